@@ -10,7 +10,7 @@ fn main() -> Result<()> {
     let chain = extract_args(&matches)?;
 
     // Create our inventory and OS.
-    let inventory = Inventory::scan();
+    let mut inventory = Inventory::scan();
     let os = inventory.builder().os_chain(chain).build()?;
 
     // Register our memflow package.
@@ -0,0 +1,625 @@
+use memflow::{
+    prelude::{MemoryView, ModuleInfo},
+    types::{umem, Address},
+};
+
+use rhai::plugin::*;
+
+use crate::{
+    memory::{map_of, read_cstr, read_raw_checked, MAX_READ_LEN},
+    process::SharedProcess,
+};
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_NOTE: u32 = 4;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_SONAME: u64 = 14;
+const DT_GNU_HASH: u64 = 0x6ffffef5;
+
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// Upper bound on the entries of any single table, guarding against garbage headers.
+const MAX_ENTRIES: usize = 0x10000;
+
+const MAX_NAME_LEN: usize = 0x400;
+
+/// Upper bound on the size of a program header or symbol, the actual ones are at most 56 bytes.
+const MAX_ENTRY_SIZE: usize = 64;
+
+/// Symbol defined by an ELF image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSymbol {
+    pub name: String,
+    pub addr: Address,
+    pub size: u64,
+    /// `STT_*` symbol type.
+    pub kind: u8,
+    /// `STB_*` symbol binding.
+    pub bind: u8,
+}
+
+impl ElfSymbol {
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            0 => "notype",
+            1 => "object",
+            2 => "func",
+            3 => "section",
+            4 => "file",
+            5 => "common",
+            6 => "tls",
+            10 => "ifunc",
+            _ => "unknown",
+        }
+    }
+
+    pub fn bind_name(&self) -> &'static str {
+        match self.bind {
+            0 => "local",
+            1 => "global",
+            2 => "weak",
+            10 => "unique",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Program header of an ELF image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+    pub align: u64,
+}
+
+/// Mapped ELF image in process memory.
+///
+/// Virtual addresses of the image are moved by `bias`, the distance between `base` and the lowest loaded segment.
+pub struct ElfImage<'a, M: MemoryView> {
+    mem: &'a mut M,
+    pub base: Address,
+    pub is_64: bool,
+    pub big_endian: bool,
+    pub bias: umem,
+    pub program_headers: Vec<ProgramHeader>,
+    pub dynamic: Vec<(u64, u64)>,
+}
+
+impl<'a, M: MemoryView> ElfImage<'a, M> {
+    /// Validate the ELF header of the image at `base` and read its program headers and dynamic section.
+    pub fn new(mem: &'a mut M, base: Address) -> Result<Self, Box<EvalAltResult>> {
+        let ident = read_raw_checked(mem, base, 16)?;
+        if ident[..4] != *b"\x7fELF" {
+            return Err(format!("no ELF signature at {:#x}", base.to_umem()).into());
+        }
+        let is_64 = match ident[4] {
+            1 => false,
+            2 => true,
+            class => return Err(format!("unknown ELF class {}", class).into()),
+        };
+        let big_endian = match ident[5] {
+            1 => false,
+            2 => true,
+            data => return Err(format!("unknown ELF data encoding {}", data).into()),
+        };
+
+        let mut image = Self {
+            mem,
+            base,
+            is_64,
+            big_endian,
+            bias: 0,
+            program_headers: vec![],
+            dynamic: vec![],
+        };
+
+        let (phoff, phentsize, phnum) = match image.is_64 {
+            true => (
+                image.u64(base + 32)?,
+                image.u16(base + 54)?,
+                image.u16(base + 56)?,
+            ),
+            false => (
+                image.u32(base + 28)?.into(),
+                image.u16(base + 42)?,
+                image.u16(base + 44)?,
+            ),
+        };
+        if phnum != 0 && !(image.phdr_size()..=MAX_ENTRY_SIZE).contains(&(phentsize as usize)) {
+            return Err(format!("invalid program header size {}", phentsize).into());
+        }
+        let len = table_len(phnum as usize, phentsize as usize, "program headers")?;
+        let raw = read_raw_checked(image.mem, addr_add(base, phoff)?, len)?;
+        image.program_headers = raw
+            .chunks_exact(phentsize.max(1) as usize)
+            .map(|ph| image.parse_phdr(ph))
+            .collect();
+
+        // The image is mapped page aligned from its lowest segment.
+        if let Some(first) = image.segments(PT_LOAD).map(|ph| ph.vaddr).min() {
+            image.bias = base.to_umem().wrapping_sub(first & !0xfff);
+        }
+        image.dynamic = image.read_dynamic()?;
+
+        Ok(image)
+    }
+
+    fn phdr_size(&self) -> usize {
+        if self.is_64 {
+            56
+        } else {
+            32
+        }
+    }
+
+    fn ptr_size(&self) -> usize {
+        if self.is_64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn uint(&self, raw: &[u8]) -> u64 {
+        let mut buf = [0u8; 8];
+        match self.big_endian {
+            true => {
+                buf[8 - raw.len()..].copy_from_slice(raw);
+                u64::from_be_bytes(buf)
+            }
+            false => {
+                buf[..raw.len()].copy_from_slice(raw);
+                u64::from_le_bytes(buf)
+            }
+        }
+    }
+
+    fn read(&mut self, addr: Address, len: usize) -> Result<u64, Box<EvalAltResult>> {
+        let raw = read_raw_checked(self.mem, addr, len)?;
+        Ok(self.uint(&raw))
+    }
+
+    fn u16(&mut self, addr: Address) -> Result<u16, Box<EvalAltResult>> {
+        Ok(self.read(addr, 2)? as u16)
+    }
+
+    fn u32(&mut self, addr: Address) -> Result<u32, Box<EvalAltResult>> {
+        Ok(self.read(addr, 4)? as u32)
+    }
+
+    fn u64(&mut self, addr: Address) -> Result<u64, Box<EvalAltResult>> {
+        self.read(addr, 8)
+    }
+
+    fn parse_phdr(&self, ph: &[u8]) -> ProgramHeader {
+        let u32_at = |off: usize| self.uint(&ph[off..off + 4]) as u32;
+        let word_at = |off: usize| self.uint(&ph[off..off + self.ptr_size()]);
+        match self.is_64 {
+            true => ProgramHeader {
+                kind: u32_at(0),
+                flags: u32_at(4),
+                offset: word_at(8),
+                vaddr: word_at(16),
+                filesz: word_at(32),
+                memsz: word_at(40),
+                align: word_at(48),
+            },
+            false => ProgramHeader {
+                kind: u32_at(0),
+                offset: word_at(4),
+                vaddr: word_at(8),
+                filesz: word_at(16),
+                memsz: word_at(20),
+                flags: u32_at(24),
+                align: word_at(28),
+            },
+        }
+    }
+
+    fn segments(&self, kind: u32) -> impl Iterator<Item = &ProgramHeader> {
+        self.program_headers
+            .iter()
+            .filter(move |ph| ph.kind == kind)
+    }
+
+    /// Address of the virtual address `vaddr` of the image.
+    fn addr(&self, vaddr: u64) -> Address {
+        Address::from(vaddr.wrapping_add(self.bias))
+    }
+
+    /// Address of a dynamic entry pointer, the loader relocates some of them in place so they may already be
+    /// absolute.
+    fn dyn_addr(&self, ptr: u64) -> Address {
+        match self.bias != 0 && ptr >= self.bias {
+            true => Address::from(ptr),
+            false => self.addr(ptr),
+        }
+    }
+
+    fn read_dynamic(&mut self) -> Result<Vec<(u64, u64)>, Box<EvalAltResult>> {
+        let dynamic = match self.segments(PT_DYNAMIC).next() {
+            Some(ph) => *ph,
+            None => return Ok(vec![]),
+        };
+        let word = self.ptr_size();
+        let count = (dynamic.memsz as usize / (word * 2)).min(MAX_ENTRIES);
+        let raw = read_raw_checked(self.mem, self.addr(dynamic.vaddr), count * word * 2)?;
+
+        Ok(raw
+            .chunks_exact(word * 2)
+            .map(|d| (self.uint(&d[..word]), self.uint(&d[word..])))
+            .take_while(|(tag, _)| *tag != DT_NULL)
+            .collect())
+    }
+
+    fn dyn_values(&self, tag: u64) -> impl Iterator<Item = u64> + '_ {
+        self.dynamic
+            .iter()
+            .filter(move |(t, _)| *t == tag)
+            .map(|(_, val)| *val)
+    }
+
+    fn dyn_value(&self, tag: u64) -> Option<u64> {
+        self.dyn_values(tag).next()
+    }
+
+    /// String at `offset` into the dynamic string table.
+    fn dyn_str(&mut self, offset: u64) -> Result<String, Box<EvalAltResult>> {
+        let strtab = self
+            .dyn_value(DT_STRTAB)
+            .ok_or("image has no dynamic string table")?;
+        if let Some(size) = self.dyn_value(DT_STRSZ) {
+            if offset >= size {
+                return Err(format!("string offset {:#x} is out of bounds", offset).into());
+            }
+        }
+        let addr = addr_add(self.dyn_addr(strtab), offset)?;
+        read_cstr(self.mem, addr, MAX_NAME_LEN)
+    }
+
+    /// ELF header fields.
+    pub fn header(&mut self) -> Result<rhai::Map, Box<EvalAltResult>> {
+        let base = self.base;
+        let (entry, flags) = match self.is_64 {
+            true => (self.u64(base + 24)?, self.u32(base + 48)?),
+            false => (self.u32(base + 24)?.into(), self.u32(base + 36)?),
+        };
+        let abi = read_raw_checked(self.mem, base + 7, 1)?[0];
+
+        Ok(map_of([
+            ("is_64", Dynamic::from_bool(self.is_64)),
+            ("big_endian", Dynamic::from_bool(self.big_endian)),
+            ("os_abi", int(abi)),
+            ("kind", int(self.u16(base + 16)?)),
+            ("machine", int(self.u16(base + 18)?)),
+            ("flags", int(flags)),
+            (
+                "entry",
+                match entry {
+                    0 => Dynamic::UNIT,
+                    entry => Dynamic::from(self.addr(entry)),
+                },
+            ),
+            ("bias", Dynamic::from_int(self.bias as rhai::INT)),
+        ]))
+    }
+
+    /// Program headers, `addr` is where the segment is mapped.
+    pub fn program_header_maps(&self) -> rhai::Array {
+        self.program_headers
+            .iter()
+            .map(|ph| {
+                Dynamic::from_map(map_of([
+                    ("kind", int(ph.kind)),
+                    ("flags", int(ph.flags)),
+                    ("offset", Dynamic::from_int(ph.offset as rhai::INT)),
+                    ("vaddr", Dynamic::from_int(ph.vaddr as rhai::INT)),
+                    ("addr", Dynamic::from(self.addr(ph.vaddr))),
+                    ("filesz", Dynamic::from_int(ph.filesz as rhai::INT)),
+                    ("memsz", Dynamic::from_int(ph.memsz as rhai::INT)),
+                    ("align", Dynamic::from_int(ph.align as rhai::INT)),
+                ]))
+            })
+            .collect()
+    }
+
+    /// Entries of the dynamic section as `tag` and `value` maps.
+    pub fn dynamic_maps(&self) -> rhai::Array {
+        self.dynamic
+            .iter()
+            .map(|(tag, val)| {
+                Dynamic::from_map(map_of([
+                    ("tag", Dynamic::from_int(*tag as rhai::INT)),
+                    ("value", Dynamic::from_int(*val as rhai::INT)),
+                ]))
+            })
+            .collect()
+    }
+
+    /// Libraries listed by `DT_NEEDED`, in load order.
+    pub fn needed(&mut self) -> Result<Vec<String>, Box<EvalAltResult>> {
+        let offsets: Vec<u64> = self.dyn_values(DT_NEEDED).collect();
+        offsets.into_iter().map(|off| self.dyn_str(off)).collect()
+    }
+
+    /// Name of the image from `DT_SONAME`.
+    pub fn soname(&mut self) -> Result<Option<String>, Box<EvalAltResult>> {
+        match self.dyn_value(DT_SONAME) {
+            Some(off) => self.dyn_str(off).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Number of entries in the dynamic symbol table, taken from the SysV or GNU hash table.
+    fn symbol_count(&mut self) -> Result<usize, Box<EvalAltResult>> {
+        if let Some(hash) = self.dyn_value(DT_HASH) {
+            // nbucket, nchain, the chain has one entry per symbol
+            let addr = addr_add(self.dyn_addr(hash), 4)?;
+            return Ok(self.u32(addr)? as usize);
+        }
+
+        let gnu_hash = match self.dyn_value(DT_GNU_HASH) {
+            Some(gnu_hash) => self.dyn_addr(gnu_hash),
+            None => return Ok(0),
+        };
+        let nbuckets = self.u32(gnu_hash)? as usize;
+        let symoffset = self.u32(addr_add(gnu_hash, 4)?)? as usize;
+        let bloom_size = self.u32(addr_add(gnu_hash, 8)?)? as usize;
+        if nbuckets > MAX_ENTRIES {
+            return Err(format!("too many hash buckets ({})", nbuckets).into());
+        }
+
+        let buckets = addr_add(gnu_hash, 16 + (bloom_size * self.ptr_size()) as umem)?;
+        let raw = read_raw_checked(self.mem, buckets, nbuckets * 4)?;
+        let last = match raw.chunks_exact(4).map(|b| self.uint(b) as usize).max() {
+            Some(last) if last >= symoffset => last,
+            _ => return Ok(symoffset),
+        };
+
+        // Walk the chain of the last bucket until the entry marking its end.
+        let chains = addr_add(buckets, (nbuckets * 4) as umem)?;
+        for idx in last..MAX_ENTRIES {
+            if self.u32(addr_add(chains, ((idx - symoffset) * 4) as umem)?)? & 1 != 0 {
+                return Ok(idx + 1);
+            }
+        }
+        Err("unterminated GNU hash chain".into())
+    }
+
+    /// Every defined symbol of the dynamic symbol table.
+    pub fn exports(&mut self) -> Result<Vec<ElfSymbol>, Box<EvalAltResult>> {
+        let symtab = match self.dyn_value(DT_SYMTAB) {
+            Some(symtab) => self.dyn_addr(symtab),
+            None => return Ok(vec![]),
+        };
+        let min_size = match self.is_64 {
+            true => 24,
+            false => 16,
+        };
+        let syment = self
+            .dyn_value(DT_SYMENT)
+            .map_or(min_size, |size| size as usize);
+        if !(min_size..=MAX_ENTRY_SIZE).contains(&syment) {
+            return Err(format!("invalid symbol size {}", syment).into());
+        }
+        let count = self.symbol_count()?;
+        if count > MAX_ENTRIES {
+            return Err(format!("too many symbols ({})", count).into());
+        }
+        let len = table_len(count, syment, "symbols")?;
+        let raw = read_raw_checked(self.mem, symtab, len)?;
+
+        let mut symbols = vec![];
+        for sym in raw.chunks_exact(syment) {
+            let (name, info, shndx, value, size) = match self.is_64 {
+                true => (
+                    self.uint(&sym[0..4]),
+                    sym[4],
+                    self.uint(&sym[6..8]),
+                    self.uint(&sym[8..16]),
+                    self.uint(&sym[16..24]),
+                ),
+                false => (
+                    self.uint(&sym[0..4]),
+                    sym[12],
+                    self.uint(&sym[14..16]),
+                    self.uint(&sym[4..8]),
+                    self.uint(&sym[8..12]),
+                ),
+            };
+            // Undefined symbols are imports, resolved from other images.
+            if name == 0 || shndx == 0 {
+                continue;
+            }
+            symbols.push(ElfSymbol {
+                name: self.dyn_str(name)?,
+                addr: self.addr(value),
+                size,
+                kind: info & 0xf,
+                bind: info >> 4,
+            });
+        }
+        Ok(symbols)
+    }
+
+    /// GNU build-id from the note segments as a lowercase hex string.
+    pub fn build_id(&mut self) -> Result<Option<String>, Box<EvalAltResult>> {
+        let notes: Vec<ProgramHeader> = self.segments(PT_NOTE).copied().collect();
+        for note in notes {
+            let raw = read_raw_checked(
+                self.mem,
+                self.addr(note.vaddr),
+                (note.memsz as usize).min(MAX_ENTRIES),
+            )?;
+
+            let mut rest = raw.as_slice();
+            while rest.len() >= 12 {
+                let namesz = self.uint(&rest[0..4]) as usize;
+                let descsz = self.uint(&rest[4..8]) as usize;
+                let kind = self.uint(&rest[8..12]) as u32;
+                let desc_start = 12 + align4(namesz);
+                let next = desc_start + align4(descsz);
+                if next > rest.len() {
+                    break;
+                }
+                if kind == NT_GNU_BUILD_ID && rest[12..12 + namesz] == *b"GNU\0" {
+                    let desc = &rest[desc_start..desc_start + descsz];
+                    return Ok(Some(desc.iter().map(|b| format!("{:02x}", b)).collect()));
+                }
+                rest = &rest[next..];
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every part of the image as a single map.
+    pub fn parse(&mut self) -> Result<rhai::Map, Box<EvalAltResult>> {
+        Ok(map_of([
+            ("base", Dynamic::from(self.base)),
+            ("header", self.header()?.into()),
+            ("program_headers", self.program_header_maps().into()),
+            ("dynamic", self.dynamic_maps().into()),
+            (
+                "needed",
+                self.needed()?
+                    .into_iter()
+                    .map(Dynamic::from)
+                    .collect::<rhai::Array>()
+                    .into(),
+            ),
+            (
+                "soname",
+                self.soname()?.map_or(Dynamic::UNIT, Dynamic::from),
+            ),
+            (
+                "exports",
+                self.exports()?
+                    .into_iter()
+                    .map(Dynamic::from)
+                    .collect::<rhai::Array>()
+                    .into(),
+            ),
+            (
+                "build_id",
+                self.build_id()?.map_or(Dynamic::UNIT, Dynamic::from),
+            ),
+        ]))
+    }
+}
+
+fn int(val: impl Into<rhai::INT>) -> Dynamic {
+    Dynamic::from_int(val.into())
+}
+
+/// Size of a table of `count` entries of `entsize` bytes to read at once, erroring if it exceeds `MAX_READ_LEN`.
+fn table_len(count: usize, entsize: usize, what: &str) -> Result<usize, Box<EvalAltResult>> {
+    count
+        .checked_mul(entsize)
+        .filter(|len| *len <= MAX_READ_LEN)
+        .ok_or_else(|| {
+            format!(
+                "malformed ELF: {} {} of {} bytes exceed the limit of {:#x} bytes",
+                count, what, entsize, MAX_READ_LEN
+            )
+            .into()
+        })
+}
+
+/// `addr + offset`, erroring on overflow instead of wrapping around the address space.
+fn addr_add(addr: Address, offset: umem) -> Result<Address, Box<EvalAltResult>> {
+    addr.to_umem()
+        .checked_add(offset)
+        .map(Address::from)
+        .ok_or_else(|| {
+            format!(
+                "malformed ELF: address {:#x} + {:#x} overflowed",
+                addr.to_umem(),
+                offset
+            )
+            .into()
+        })
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// ELF image functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod elf_functions {
+    /// Parse the ELF image mapped at `base`.
+    #[rhai_fn(pure, global, return_raw, name = "elf")]
+    pub fn parse_elf(
+        proc: &mut SharedProcess,
+        base: Address,
+    ) -> Result<rhai::Map, Box<EvalAltResult>> {
+        ElfImage::new(proc.get_mut(), base)?.parse()
+    }
+
+    /// Parse the ELF image of `module`.
+    #[rhai_fn(pure, global, return_raw, name = "elf")]
+    pub fn parse_elf_module(
+        proc: &mut SharedProcess,
+        module: ModuleInfo,
+    ) -> Result<rhai::Map, Box<EvalAltResult>> {
+        parse_elf(proc, module.base)
+    }
+
+    /// ELF symbol getters.
+    pub mod elf_symbol_functions {
+        /// Name of the symbol.
+        #[rhai_fn(pure, global, get = "name")]
+        pub fn get_name(sym: &mut ElfSymbol) -> String {
+            sym.name.clone()
+        }
+
+        /// Address of the symbol.
+        #[rhai_fn(pure, global, get = "addr")]
+        pub fn get_addr(sym: &mut ElfSymbol) -> Address {
+            sym.addr
+        }
+
+        /// Size of the symbol in bytes.
+        #[rhai_fn(pure, global, get = "size")]
+        pub fn get_size(sym: &mut ElfSymbol) -> rhai::INT {
+            sym.size as rhai::INT
+        }
+
+        /// Symbol type such as `"func"` or `"object"`.
+        #[rhai_fn(pure, global, get = "kind")]
+        pub fn get_kind(sym: &mut ElfSymbol) -> String {
+            sym.kind_name().into()
+        }
+
+        /// Symbol binding such as `"global"` or `"weak"`.
+        #[rhai_fn(pure, global, get = "bind")]
+        pub fn get_bind(sym: &mut ElfSymbol) -> String {
+            sym.bind_name().into()
+        }
+
+        /// Display the symbol name, type and address.
+        #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+        pub fn to_string(sym: &mut ElfSymbol) -> String {
+            format!(
+                "ElfSymbol({} {} @ {:#x})",
+                sym.kind_name(),
+                sym.name,
+                sym.addr.to_umem()
+            )
+        }
+    }
+}
@@ -4,24 +4,68 @@
 use rhai::def_package;
 use rhai::plugin::*;
 
+pub mod elf;
 pub mod memory;
 pub mod native;
 pub mod os;
+pub mod path;
+pub mod pattern;
+pub mod pe;
 pub mod process;
+pub mod region;
+pub mod scanner;
+pub mod view;
 
+use crate::elf::elf_functions;
 use crate::memory::memory_functions;
 use crate::os::os_functions;
+use crate::path::path_functions;
+use crate::pattern::pattern_functions;
+use crate::pe::pe_functions;
 use crate::process::process_functions;
+use crate::region::region_functions;
+use crate::scanner::scanner_functions;
+use crate::view::view_functions;
 
 def_package! {
     /// Package for memory introspection with memflow
     pub MemflowPackage(lib) {
         lib.set_custom_type::<process::SharedProcess>("Process");
+        lib.set_custom_type::<process::ModuleExport>("ModuleExport");
+        lib.set_custom_type::<process::ModuleImport>("ModuleImport");
+        lib.set_custom_type::<memory::U64>("U64");
+        lib.set_custom_type::<memory::ReadError>("ReadError");
+        lib.set_custom_type::<view::View>("View");
+        lib.set_custom_type::<scanner::Scanner>("Scanner");
+        lib.set_custom_type::<region::MemoryRegion>("MemoryRegion");
+        lib.set_custom_type::<elf::ElfSymbol>("ElfSymbol");
+        lib.set_custom_type::<native::EnumValue>("EnumValue");
         combine_with_exported_module!(lib, "rhai_memflow_native", native::export_mod);
         combine_with_exported_module!(lib, "rhai_memflow_memory", memory_functions);
         combine_with_exported_module!(lib, "rhai_memflow_os", os_functions);
         combine_with_exported_module!(lib, "rhai_memflow_process", process_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_path", path_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_region", region_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_pattern", pattern_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_pe", pe_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_elf", elf_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_view", view_functions);
+        combine_with_exported_module!(lib, "rhai_memflow_scanner", scanner_functions);
     } |> |engine| {
         native::register_native_syntax(engine);
     }
 }
+
+impl MemflowPackage {
+    /// Longest `Collection` whose length is read from a field, longer ones fail to read.
+    pub fn max_collection_len() -> u32 {
+        memory::max_collection_len()
+    }
+
+    /// Set the longest `Collection` whose length is read from a field.
+    ///
+    /// The limit is shared by every engine in the process and can only be changed from Rust.
+    pub fn set_max_collection_len(len: u32) {
+        memory::set_max_collection_len(len)
+    }
+}
@@ -1,22 +1,82 @@
-use std::ops::Add;
-use std::str::FromStr;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
 
+use memflow::cglue::tuple::CTup2;
+use memflow::mem::ReadData;
 use memflow::prelude::MemoryView;
-use memflow::types::Address;
+use memflow::types::{umem, Address};
 
 use rhai::plugin::*;
 use widestring::U16String;
 
-use super::native::Type;
+use super::native::{Enum, EnumValue, Field, Struct, Type};
 
 /*
-    When reading i32, u32, u8, u16, u64 you get back an i64 right now,
+    When reading i32, u32, u8, u16 you get back an i64 right now,
     this is just to insure you can actually use the numbers (i64 is the system INT for rhai),
     weirdly rhai seems to support operations between numbers, are we doing something wrong?
+
+    A u64 does not fit into an i64 so it is instead read back as a `U64`.
 */
 
 pub type NativePointer = (Box<Type>, Address);
 
+/// Longest `Collection` whose length is read from memory, so garbage lengths cannot trigger huge reads.
+static MAX_COLLECTION_LEN: AtomicU32 = AtomicU32::new(0x10000);
+
+/// Longest `Collection` whose length is read from a field.
+pub fn max_collection_len() -> u32 {
+    MAX_COLLECTION_LEN.load(Ordering::Relaxed)
+}
+
+/// Set the longest `Collection` whose length is read from a field for every engine in this process.
+pub(crate) fn set_max_collection_len(len: u32) {
+    MAX_COLLECTION_LEN.store(len, Ordering::Relaxed);
+}
+
+/// Lossless unsigned 64-bit integer, `rhai::INT` is signed and cannot hold values with the top bit set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct U64(pub u64);
+
+/// Error for a single failed read of a batch, returned in place of the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadError {
+    pub addr: Address,
+    pub message: String,
+}
+
+/// Build a Rhai map from `(key, value)` pairs.
+pub fn map_of<const N: usize>(entries: [(&str, Dynamic); N]) -> rhai::Map {
+    entries
+        .into_iter()
+        .map(|(key, val)| (key.into(), val))
+        .collect()
+}
+
+/// Offset `addr` by the signed `offset`, erroring instead of wrapping around the address space.
+pub fn offset_addr(addr: Address, offset: rhai::INT) -> Result<Address, Box<EvalAltResult>> {
+    match offset < 0 {
+        true => addr.to_umem().checked_sub(offset.unsigned_abs()),
+        false => addr.to_umem().checked_add(offset as u64),
+    }
+    .map(Address::from)
+    .ok_or_else(|| format!("address {:#x} + {} overflowed", addr.to_umem(), offset).into())
+}
+
+/// Byte offset of `count` elements of `ty`.
+pub fn element_offset(ty: &Type, count: rhai::INT) -> Result<rhai::INT, Box<EvalAltResult>> {
+    count
+        .checked_mul(ty.size().into())
+        .ok_or_else(|| format!("offset of {} `{}` elements overflowed", count, ty.name()).into())
+}
+
+fn align_mask(align: rhai::INT) -> Result<u64, Box<EvalAltResult>> {
+    match u64::try_from(align) {
+        Ok(align) if align.is_power_of_two() => Ok(align - 1),
+        _ => Err(format!("alignment {} is not a power of two", align).into()),
+    }
+}
+
 /// Memory functions.
 #[export_module]
 #[allow(dead_code)]
@@ -25,15 +85,47 @@ pub mod memory_functions {
     pub mod address_functions {
         use memflow::types::Address;
 
-        pub fn addr(num: rhai::INT) -> Address {
-            Address::from(num.abs())
+        /// Create an address from a non-negative number.
+        #[rhai_fn(return_raw, name = "addr")]
+        pub fn addr(num: rhai::INT) -> Result<Address, Box<EvalAltResult>> {
+            u64::try_from(num)
+                .map(Address::from)
+                .map_err(|_| format!("cannot create address from negative number {}", num).into())
         }
 
-        #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+        /// Create an address from a hexadecimal string, optionally prefixed with `0x` (i.e. `0x7ff6`12345678`).
+        #[rhai_fn(return_raw, name = "addr")]
+        pub fn addr_from_str(str: &str) -> Result<Address, Box<EvalAltResult>> {
+            let hex: String = str
+                .trim()
+                .trim_start_matches("0x")
+                .trim_start_matches("0X")
+                .chars()
+                .filter(|c| *c != '`')
+                .collect();
+            u64::from_str_radix(&hex, 16)
+                .map(Address::from)
+                .map_err(|e| format!("cannot parse `{}` as address: {}", str, e).into())
+        }
+
+        /// Format as `0x` prefixed hexadecimal.
+        #[rhai_fn(pure, global, name = "to_string")]
         pub fn to_string(addr: &mut Address) -> String {
+            format!("{:#x}", addr.to_umem())
+        }
+
+        /// Format with the `Debug` representation of `Address`.
+        #[rhai_fn(pure, global, name = "to_debug")]
+        pub fn to_debug(addr: &mut Address) -> String {
             format!("{:?}", addr)
         }
 
+        /// Return `true` if the address is null.
+        #[rhai_fn(pure, global)]
+        pub fn is_null(addr: &mut Address) -> bool {
+            addr.is_null()
+        }
+
         /// Return `true` if two addresses are equal.
         #[rhai_fn(pure, global, name = "==")]
         pub fn eq(addr: &mut Address, addr2: Address) -> bool {
@@ -43,13 +135,136 @@ pub mod memory_functions {
         /// Return `true` if address equals the `num` number.
         #[rhai_fn(pure, global, name = "==")]
         pub fn eq_num(addr: &mut Address, num: rhai::INT) -> bool {
-            addr.to_umem() == num.unsigned_abs()
+            addr.to_umem() as i128 == num as i128
+        }
+
+        /// Return `true` if two addresses are not equal.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq(addr: &mut Address, addr2: Address) -> bool {
+            *addr != addr2
+        }
+
+        /// Return `true` if address does not equal the `num` number.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq_num(addr: &mut Address, num: rhai::INT) -> bool {
+            addr.to_umem() as i128 != num as i128
+        }
+
+        /// Return `true` if the address is lower than `addr2`.
+        #[rhai_fn(pure, global, name = "<")]
+        pub fn lt(addr: &mut Address, addr2: Address) -> bool {
+            *addr < addr2
+        }
+
+        /// Return `true` if the address is higher than `addr2`.
+        #[rhai_fn(pure, global, name = ">")]
+        pub fn gt(addr: &mut Address, addr2: Address) -> bool {
+            *addr > addr2
+        }
+
+        /// Return `true` if the address is lower than or equal to `addr2`.
+        #[rhai_fn(pure, global, name = "<=")]
+        pub fn lte(addr: &mut Address, addr2: Address) -> bool {
+            *addr <= addr2
+        }
+
+        /// Return `true` if the address is higher than or equal to `addr2`.
+        #[rhai_fn(pure, global, name = ">=")]
+        pub fn gte(addr: &mut Address, addr2: Address) -> bool {
+            *addr >= addr2
+        }
+
+        /// Return an address which is offset from the original address, `offset` may be negative.
+        #[rhai_fn(pure, global, return_raw, name = "+")]
+        pub fn add_num(
+            addr: &mut Address,
+            offset: rhai::INT,
+        ) -> Result<Address, Box<EvalAltResult>> {
+            offset_addr(*addr, offset)
+        }
+
+        /// Return an address which is offset backwards from the original address.
+        #[rhai_fn(pure, global, return_raw, name = "-")]
+        pub fn sub_num(
+            addr: &mut Address,
+            offset: rhai::INT,
+        ) -> Result<Address, Box<EvalAltResult>> {
+            match offset.checked_neg() {
+                Some(offset) => offset_addr(*addr, offset),
+                None => {
+                    Err(format!("address {:#x} - {} overflowed", addr.to_umem(), offset).into())
+                }
+            }
+        }
+
+        /// Return the signed distance in bytes between two addresses.
+        #[rhai_fn(pure, global, return_raw, name = "-")]
+        pub fn sub(addr: &mut Address, addr2: Address) -> Result<rhai::INT, Box<EvalAltResult>> {
+            rhai::INT::try_from(addr.to_umem() as i128 - addr2.to_umem() as i128).map_err(|_| {
+                format!(
+                    "distance between {:#x} and {:#x} is out of range for `INT`",
+                    addr.to_umem(),
+                    addr2.to_umem()
+                )
+                .into()
+            })
+        }
+
+        /// Return the bitwise and of the address and `mask`.
+        #[rhai_fn(pure, global, name = "&")]
+        pub fn and(addr: &mut Address, mask: rhai::INT) -> Address {
+            Address::from(addr.to_umem() & mask as u64)
+        }
+
+        /// Return the bitwise or of the address and `mask`.
+        #[rhai_fn(pure, global, name = "|")]
+        pub fn or(addr: &mut Address, mask: rhai::INT) -> Address {
+            Address::from(addr.to_umem() | mask as u64)
+        }
+
+        /// Shift the address right, bits shifted past the bottom are discarded.
+        #[rhai_fn(pure, global, name = ">>")]
+        pub fn shr(addr: &mut Address, bits: rhai::INT) -> Address {
+            Address::from(
+                u32::try_from(bits)
+                    .ok()
+                    .and_then(|bits| addr.to_umem().checked_shr(bits))
+                    .unwrap_or(0),
+            )
+        }
+
+        /// Shift the address left, bits shifted past the top are discarded.
+        #[rhai_fn(pure, global, name = "<<")]
+        pub fn shl(addr: &mut Address, bits: rhai::INT) -> Address {
+            Address::from(
+                u32::try_from(bits)
+                    .ok()
+                    .and_then(|bits| addr.to_umem().checked_shl(bits))
+                    .unwrap_or(0),
+            )
         }
 
-        /// Return an address which is offset from the original address.
-        #[rhai_fn(pure, global, name = "+")]
-        pub fn add_num(addr: &mut Address, offset: rhai::INT) -> Address {
-            addr.add(offset.abs())
+        /// Round the address up to the next multiple of `align`, which must be a power of two.
+        #[rhai_fn(pure, global, return_raw)]
+        pub fn align_up(
+            addr: &mut Address,
+            align: rhai::INT,
+        ) -> Result<Address, Box<EvalAltResult>> {
+            let mask = align_mask(align)?;
+            addr.to_umem()
+                .checked_add(mask)
+                .map(|num| Address::from(num & !mask))
+                .ok_or_else(|| format!("aligning {:#x} up overflowed", addr.to_umem()).into())
+        }
+
+        /// Round the address down to the previous multiple of `align`, which must be a power of two.
+        #[rhai_fn(pure, global, return_raw)]
+        pub fn align_down(
+            addr: &mut Address,
+            align: rhai::INT,
+        ) -> Result<Address, Box<EvalAltResult>> {
+            let mask = align_mask(align)?;
+            Ok(Address::from(addr.to_umem() & !mask))
         }
     }
 
@@ -83,7 +298,8 @@ pub mod memory_functions {
         /// Return `true` if pointer's address equals the `num` number.
         #[rhai_fn(pure, global, name = "==")]
         pub fn eq_num_addr((_, addr): &mut NativePointer, num: rhai::INT) -> bool {
-            addr.to_umem() == num.unsigned_abs()
+            // Negative numbers are never a valid address, the same as for `Address`.
+            addr.to_umem() as i128 == num as i128
         }
 
         /// Return `true` if pointer equals the `ty2` type.
@@ -92,211 +308,1181 @@ pub mod memory_functions {
             **ty == ty2
         }
 
-        /// Return a pointer which is offset from the original pointer.
-        #[rhai_fn(global, name = "+")]
-        pub fn add_num((ty, addr): NativePointer, offset: rhai::INT) -> NativePointer {
-            (ty, addr.add(offset.abs()))
+        /// Return a pointer which is offset by `count` elements of the pointee type.
+        #[rhai_fn(global, return_raw, name = "+")]
+        pub fn add_num(
+            (ty, addr): NativePointer,
+            count: rhai::INT,
+        ) -> Result<NativePointer, Box<EvalAltResult>> {
+            let addr = offset_addr(addr, element_offset(&ty, count)?)?;
+            Ok((ty, addr))
+        }
+
+        /// Return a pointer which is offset backwards by `count` elements of the pointee type.
+        #[rhai_fn(global, return_raw, name = "-")]
+        pub fn sub_num(
+            (ty, mut addr): NativePointer,
+            count: rhai::INT,
+        ) -> Result<NativePointer, Box<EvalAltResult>> {
+            let addr = super::address_functions::sub_num(&mut addr, element_offset(&ty, count)?)?;
+            Ok((ty, addr))
+        }
+
+        /// Return the signed distance in elements between two pointers of the same type.
+        #[rhai_fn(pure, global, return_raw, name = "-")]
+        pub fn sub(
+            (ty, addr): &mut NativePointer,
+            (ty2, addr2): NativePointer,
+        ) -> Result<rhai::INT, Box<EvalAltResult>> {
+            if *ty != ty2 {
+                return Err(format!(
+                    "cannot subtract pointers to `{}` and `{}`",
+                    ty.name(),
+                    ty2.name()
+                )
+                .into());
+            }
+
+            let size = rhai::INT::from(ty.size());
+            let distance = super::address_functions::sub(addr, addr2)?;
+            match (size, distance % size.max(1)) {
+                (0, _) => {
+                    Err(format!("cannot subtract pointers to zero sized `{}`", ty.name()).into())
+                }
+                (_, 0) => Ok(distance / size),
+                _ => Err(format!(
+                    "distance of {} bytes is not a multiple of `{}` size {}",
+                    distance,
+                    ty.name(),
+                    size
+                )
+                .into()),
+            }
+        }
+
+        /// Return a pointer to the element at `index`, the element itself is read with `proc.view(ptr)[index]`.
+        #[rhai_fn(pure, global, return_raw)]
+        pub fn offset(
+            ptr: &mut NativePointer,
+            index: rhai::INT,
+        ) -> Result<NativePointer, Box<EvalAltResult>> {
+            add_num(ptr.clone(), index)
+        }
+
+        /// Return a pointer which is offset by `offset` bytes, regardless of the pointee type.
+        #[rhai_fn(pure, global, return_raw)]
+        pub fn byte_offset(
+            (ty, addr): &mut NativePointer,
+            offset: rhai::INT,
+        ) -> Result<NativePointer, Box<EvalAltResult>> {
+            Ok((ty.clone(), offset_addr(*addr, offset)?))
+        }
+    }
+
+    pub mod blob_functions {
+        /// Decode a value of `ty` from `blob` at `offset`.
+        #[rhai_fn(return_raw, name = "decode")]
+        pub fn decode(
+            ty: Type,
+            blob: rhai::Blob,
+            offset: rhai::INT,
+        ) -> Result<Dynamic, Box<EvalAltResult>> {
+            match usize::try_from(offset)
+                .ok()
+                .and_then(|offset| blob.get(offset..))
+            {
+                Some(buf) if ty.is_dynamic() => decode_dyn(&resolve_lengths(&ty, buf)?, buf),
+                Some(buf) => decode_dyn(&ty, buf),
+                None => Err(format!(
+                    "offset {} is out of bounds for blob of {} bytes",
+                    offset,
+                    blob.len()
+                )
+                .into()),
+            }
+        }
+
+        /// Decode a value of `ty` from the start of `blob`.
+        #[rhai_fn(return_raw, name = "decode")]
+        pub fn decode_start(ty: Type, blob: rhai::Blob) -> Result<Dynamic, Box<EvalAltResult>> {
+            decode(ty, blob, 0)
+        }
+
+        /// Encode `val` as `ty` into a new blob, pointers are encoded as their address.
+        #[rhai_fn(return_raw, name = "encode")]
+        pub fn encode(ty: Type, val: Dynamic) -> Result<rhai::Blob, Box<EvalAltResult>> {
+            let mut blob = vec![0; byte_len(&ty)?];
+            encode_dyn(&ty, val, &mut blob)?;
+            Ok(blob)
+        }
+    }
+
+    pub mod collection_functions {
+        /// Longest `Collection` whose length is read from a field.
+        #[rhai_fn(name = "max_collection_len")]
+        pub fn get_max_collection_len() -> rhai::INT {
+            crate::memory::max_collection_len().into()
+        }
+    }
+
+    pub mod read_error_functions {
+        /// Return the address which failed to be read.
+        #[rhai_fn(pure, get = "addr")]
+        pub fn get_addr(err: &mut ReadError) -> Address {
+            err.addr
+        }
+
+        /// Return the reason the read failed.
+        #[rhai_fn(pure, get = "message")]
+        pub fn get_message(err: &mut ReadError) -> String {
+            err.message.clone()
+        }
+
+        /// Format as `ReadError(<addr>: <message>)`.
+        #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+        pub fn to_string(err: &mut ReadError) -> String {
+            format!("ReadError({:#x}: {})", err.addr.to_umem(), err.message)
+        }
+    }
+
+    pub mod u64_functions {
+        /// Create a `U64` from a non-negative number.
+        #[rhai_fn(return_raw, name = "u64")]
+        pub fn u64_from_num(num: rhai::INT) -> Result<U64, Box<EvalAltResult>> {
+            u64::try_from(num)
+                .map(U64)
+                .map_err(|_| format!("cannot create `U64` from negative number {}", num).into())
+        }
+
+        /// Create a `U64` from a decimal or `0x` prefixed hexadecimal string.
+        #[rhai_fn(return_raw, name = "u64")]
+        pub fn u64_from_str(str: &str) -> Result<U64, Box<EvalAltResult>> {
+            let str = str.trim();
+            match str.strip_prefix("0x").or_else(|| str.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => str.parse::<u64>(),
+            }
+            .map(U64)
+            .map_err(|e| format!("cannot parse `{}` as `U64`: {}", str, e).into())
+        }
+
+        /// Create a `U64` from an address.
+        #[rhai_fn(name = "u64")]
+        pub fn u64_from_addr(addr: Address) -> U64 {
+            U64(addr.to_umem())
+        }
+
+        /// Convert to a number, erroring if the value does not fit into one.
+        #[rhai_fn(pure, global, return_raw)]
+        pub fn to_int(num: &mut U64) -> Result<rhai::INT, Box<EvalAltResult>> {
+            rhai::INT::try_from(num.0)
+                .map_err(|_| format!("value {} is out of range for `INT`", num.0).into())
+        }
+
+        /// Convert to a floating point number, this may lose precision.
+        #[rhai_fn(pure, global)]
+        pub fn to_float(num: &mut U64) -> rhai::FLOAT {
+            num.0 as rhai::FLOAT
+        }
+
+        /// Format as `0x` prefixed hexadecimal.
+        #[rhai_fn(pure, global)]
+        pub fn to_hex(num: &mut U64) -> String {
+            format!("{:#x}", num.0)
+        }
+
+        /// Format as a decimal number.
+        #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+        pub fn to_string(num: &mut U64) -> String {
+            num.0.to_string()
+        }
+
+        /// Return the sum, erroring on overflow.
+        #[rhai_fn(pure, global, return_raw, name = "+")]
+        pub fn add(num: &mut U64, num2: U64) -> Result<U64, Box<EvalAltResult>> {
+            num.0
+                .checked_add(num2.0)
+                .map(U64)
+                .ok_or_else(|| "`U64` addition overflowed".into())
+        }
+
+        /// Return the sum, erroring on overflow.
+        #[rhai_fn(pure, global, return_raw, name = "+")]
+        pub fn add_num(num: &mut U64, num2: rhai::INT) -> Result<U64, Box<EvalAltResult>> {
+            match num2 < 0 {
+                true => num.0.checked_sub(num2.unsigned_abs()),
+                false => num.0.checked_add(num2 as u64),
+            }
+            .map(U64)
+            .ok_or_else(|| "`U64` addition overflowed".into())
+        }
+
+        /// Return the difference, erroring on underflow.
+        #[rhai_fn(pure, global, return_raw, name = "-")]
+        pub fn sub(num: &mut U64, num2: U64) -> Result<U64, Box<EvalAltResult>> {
+            num.0
+                .checked_sub(num2.0)
+                .map(U64)
+                .ok_or_else(|| "`U64` subtraction underflowed".into())
+        }
+
+        /// Return the difference, erroring on underflow.
+        #[rhai_fn(pure, global, return_raw, name = "-")]
+        pub fn sub_num(num: &mut U64, num2: rhai::INT) -> Result<U64, Box<EvalAltResult>> {
+            match num2 < 0 {
+                true => num.0.checked_add(num2.unsigned_abs()),
+                false => num.0.checked_sub(num2 as u64),
+            }
+            .map(U64)
+            .ok_or_else(|| "`U64` subtraction underflowed".into())
+        }
+
+        /// Return the product, erroring on overflow.
+        #[rhai_fn(pure, global, return_raw, name = "*")]
+        pub fn mul(num: &mut U64, num2: U64) -> Result<U64, Box<EvalAltResult>> {
+            num.0
+                .checked_mul(num2.0)
+                .map(U64)
+                .ok_or_else(|| "`U64` multiplication overflowed".into())
+        }
+
+        /// Return the product, erroring on overflow.
+        #[rhai_fn(pure, global, return_raw, name = "*")]
+        pub fn mul_num(num: &mut U64, num2: rhai::INT) -> Result<U64, Box<EvalAltResult>> {
+            mul(num, u64_from_num(num2)?)
+        }
+
+        /// Return the quotient, erroring on division by zero.
+        #[rhai_fn(pure, global, return_raw, name = "/")]
+        pub fn div(num: &mut U64, num2: U64) -> Result<U64, Box<EvalAltResult>> {
+            num.0
+                .checked_div(num2.0)
+                .map(U64)
+                .ok_or_else(|| "`U64` division by zero".into())
+        }
+
+        /// Return the quotient, erroring on division by zero.
+        #[rhai_fn(pure, global, return_raw, name = "/")]
+        pub fn div_num(num: &mut U64, num2: rhai::INT) -> Result<U64, Box<EvalAltResult>> {
+            div(num, u64_from_num(num2)?)
+        }
+
+        /// Return the remainder, erroring on division by zero.
+        #[rhai_fn(pure, global, return_raw, name = "%")]
+        pub fn rem(num: &mut U64, num2: U64) -> Result<U64, Box<EvalAltResult>> {
+            num.0
+                .checked_rem(num2.0)
+                .map(U64)
+                .ok_or_else(|| "`U64` division by zero".into())
+        }
+
+        /// Return the remainder, erroring on division by zero.
+        #[rhai_fn(pure, global, return_raw, name = "%")]
+        pub fn rem_num(num: &mut U64, num2: rhai::INT) -> Result<U64, Box<EvalAltResult>> {
+            rem(num, u64_from_num(num2)?)
+        }
+
+        /// Return the bitwise and.
+        #[rhai_fn(pure, global, name = "&")]
+        pub fn and(num: &mut U64, num2: U64) -> U64 {
+            U64(num.0 & num2.0)
+        }
+
+        /// Return the bitwise and.
+        #[rhai_fn(pure, global, name = "&")]
+        pub fn and_num(num: &mut U64, num2: rhai::INT) -> U64 {
+            U64(num.0 & num2 as u64)
+        }
+
+        /// Return the bitwise or.
+        #[rhai_fn(pure, global, name = "|")]
+        pub fn or(num: &mut U64, num2: U64) -> U64 {
+            U64(num.0 | num2.0)
+        }
+
+        /// Return the bitwise or.
+        #[rhai_fn(pure, global, name = "|")]
+        pub fn or_num(num: &mut U64, num2: rhai::INT) -> U64 {
+            U64(num.0 | num2 as u64)
+        }
+
+        /// Return the bitwise xor.
+        #[rhai_fn(pure, global, name = "^")]
+        pub fn xor(num: &mut U64, num2: U64) -> U64 {
+            U64(num.0 ^ num2.0)
+        }
+
+        /// Return the bitwise xor.
+        #[rhai_fn(pure, global, name = "^")]
+        pub fn xor_num(num: &mut U64, num2: rhai::INT) -> U64 {
+            U64(num.0 ^ num2 as u64)
+        }
+
+        /// Shift left, bits shifted past the top are discarded.
+        #[rhai_fn(pure, global, name = "<<")]
+        pub fn shl(num: &mut U64, bits: rhai::INT) -> U64 {
+            U64(u32::try_from(bits)
+                .ok()
+                .and_then(|bits| num.0.checked_shl(bits))
+                .unwrap_or(0))
+        }
+
+        /// Shift right, bits shifted past the bottom are discarded.
+        #[rhai_fn(pure, global, name = ">>")]
+        pub fn shr(num: &mut U64, bits: rhai::INT) -> U64 {
+            U64(u32::try_from(bits)
+                .ok()
+                .and_then(|bits| num.0.checked_shr(bits))
+                .unwrap_or(0))
+        }
+
+        /// Return `true` if two numbers are equal.
+        #[rhai_fn(pure, global, name = "==")]
+        pub fn eq(num: &mut U64, num2: U64) -> bool {
+            *num == num2
+        }
+
+        /// Return `true` if two numbers are equal.
+        #[rhai_fn(pure, global, name = "==")]
+        pub fn eq_num(num: &mut U64, num2: rhai::INT) -> bool {
+            num.0 as i128 == num2 as i128
+        }
+
+        /// Return `true` if two numbers are not equal.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq(num: &mut U64, num2: U64) -> bool {
+            *num != num2
+        }
+
+        /// Return `true` if two numbers are not equal.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq_num(num: &mut U64, num2: rhai::INT) -> bool {
+            num.0 as i128 != num2 as i128
+        }
+
+        /// Return `true` if the number is less than `num2`.
+        #[rhai_fn(pure, global, name = "<")]
+        pub fn lt(num: &mut U64, num2: U64) -> bool {
+            *num < num2
+        }
+
+        /// Return `true` if the number is less than `num2`.
+        #[rhai_fn(pure, global, name = "<")]
+        pub fn lt_num(num: &mut U64, num2: rhai::INT) -> bool {
+            (num.0 as i128) < num2 as i128
+        }
+
+        /// Return `true` if the number is greater than `num2`.
+        #[rhai_fn(pure, global, name = ">")]
+        pub fn gt(num: &mut U64, num2: U64) -> bool {
+            *num > num2
+        }
+
+        /// Return `true` if the number is greater than `num2`.
+        #[rhai_fn(pure, global, name = ">")]
+        pub fn gt_num(num: &mut U64, num2: rhai::INT) -> bool {
+            num.0 as i128 > num2 as i128
+        }
+
+        /// Return `true` if the number is less than or equal to `num2`.
+        #[rhai_fn(pure, global, name = "<=")]
+        pub fn lte(num: &mut U64, num2: U64) -> bool {
+            *num <= num2
+        }
+
+        /// Return `true` if the number is less than or equal to `num2`.
+        #[rhai_fn(pure, global, name = "<=")]
+        pub fn lte_num(num: &mut U64, num2: rhai::INT) -> bool {
+            num.0 as i128 <= num2 as i128
+        }
+
+        /// Return `true` if the number is greater than or equal to `num2`.
+        #[rhai_fn(pure, global, name = ">=")]
+        pub fn gte(num: &mut U64, num2: U64) -> bool {
+            *num >= num2
+        }
+
+        /// Return `true` if the number is greater than or equal to `num2`.
+        #[rhai_fn(pure, global, name = ">=")]
+        pub fn gte_num(num: &mut U64, num2: rhai::INT) -> bool {
+            num.0 as i128 >= num2 as i128
         }
     }
 }
 
 // TODO: Write `From` helpers to these fns
 
+/// Read a value of `ty` at `addr`, contiguous types are fetched with a single read and then decoded.
 pub fn read_to_dyn(
     mem: &mut impl MemoryView,
     ty: &Type,
     addr: Address,
 ) -> Result<Dynamic, Box<EvalAltResult>> {
-    match ty {
-        Type::UInt8 => match mem.read::<u8>(addr) {
-            Ok(uint) => Ok(Dynamic::from_int(uint as rhai::INT)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::UInt16 => match mem.read::<u16>(addr) {
-            Ok(uint) => Ok(Dynamic::from_int(uint as rhai::INT)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Int32 => match mem.read::<i32>(addr) {
-            Ok(int) => Ok(Dynamic::from_int(int as rhai::INT)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::UInt32 => match mem.read::<u32>(addr) {
-            Ok(uint) => Ok(Dynamic::from_int(uint as rhai::INT)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Fp32 => match mem.read::<f32>(addr) {
-            Ok(fp) => Ok(Dynamic::from_float(fp as f64)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Address32 => match mem.read_addr32(addr) {
-            Ok(addr) => Ok(Dynamic::from(addr)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Pointer32(ty) => match mem.read_addr32(addr) {
-            Ok(addr) => Ok(Dynamic::from((ty.clone(), addr))),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Int64 => match mem.read::<i64>(addr) {
-            Ok(int) => Ok(Dynamic::from_int(int)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        // TODO: u64 -> i64 is very bad if the u64 num sets the sign bit, fix!
-        Type::UInt64 => match mem.read::<u64>(addr) {
-            Ok(uint) => Ok(Dynamic::from_int(uint as rhai::INT)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Fp64 => match mem.read::<f64>(addr) {
-            Ok(fp) => Ok(Dynamic::from_float(fp)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Address64 => match mem.read_addr64(addr) {
-            Ok(addr) => Ok(Dynamic::from(addr)),
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::Pointer64(ty) => match mem.read_addr64(addr) {
-            Ok(addr) => Ok(Dynamic::from((ty.clone(), addr))),
-            Err(e) => Err(e.as_str().into()),
+    if ty.is_dynamic() {
+        let ty = resolve_type(mem, ty, addr)?;
+        return read_to_dyn(mem, &ty, addr);
+    }
+
+    match read_raw_checked(mem, addr, byte_len(ty)?) {
+        Ok(raw) => decode_dyn(ty, &raw),
+        Err(_) => Err(format!("failed to read `{}` at {:#x}", ty.name(), addr.to_umem()).into()),
+    }
+}
+
+/// Read every `(ty, addr)` request with a single batch, failed requests are returned as a `ReadError` in place of
+/// their value without affecting the rest of the batch.
+pub fn read_many_to_dyn(
+    mem: &mut impl MemoryView,
+    reqs: &[(Type, Address)],
+) -> Result<rhai::Array, Box<EvalAltResult>> {
+    let raw_reqs = reqs
+        .iter()
+        .map(|(ty, addr)| Ok((*addr, byte_len(ty)?)))
+        .collect::<Result<Vec<_>, Box<EvalAltResult>>>()?;
+
+    Ok(reqs
+        .iter()
+        .zip(read_raw_batch(mem, &raw_reqs)?)
+        .map(|((ty, addr), raw)| {
+            let res = match raw {
+                // Reading the fixed part first only tells how much more has to be read.
+                Some(_) if ty.is_dynamic() => {
+                    read_to_dyn(mem, ty, *addr).map_err(|e| e.to_string())
+                }
+                Some(raw) => decode_dyn(ty, &raw).map_err(|e| e.to_string()),
+                None => Err(format!(
+                    "failed to read `{}` at {:#x}",
+                    ty.name(),
+                    addr.to_umem()
+                )),
+            };
+            res.unwrap_or_else(|message| {
+                Dynamic::from(ReadError {
+                    addr: *addr,
+                    message,
+                })
+            })
+        })
+        .collect())
+}
+
+/// `ty` with the lengths of its `DynCollection`s read from the struct at `addr`.
+pub fn resolve_type(
+    mem: &mut impl MemoryView,
+    ty: &Type,
+    addr: Address,
+) -> Result<Type, Box<EvalAltResult>> {
+    match read_raw_checked(mem, addr, byte_len(ty)?) {
+        Ok(raw) => resolve_lengths(ty, &raw),
+        Err(_) => Err(format!("failed to read `{}` at {:#x}", ty.name(), addr.to_umem()).into()),
+    }
+}
+
+/// `ty` with every `DynCollection` replaced by a `Collection` of the length its sibling field has in `buf`,
+/// including those behind pointers and within nested structs.
+pub fn resolve_lengths(ty: &Type, buf: &[u8]) -> Result<Type, Box<EvalAltResult>> {
+    let n = match ty {
+        Type::Struct(n) => n,
+        Type::DynCollection(_, len_field) => return Err(no_length_field(len_field)),
+        Type::Pointer32(pty) | Type::Pointer64(pty) => match &**pty {
+            Type::DynCollection(_, len_field) => return Err(no_length_field(len_field)),
+            _ => return Ok(ty.clone()),
         },
-        Type::String(len) => match mem.read_char_array(addr, *len as usize) {
-            Ok(strn) => match Dynamic::from_str(&strn) {
-                Ok(dystr) => Ok(dystr),
-                Err(_) => Err("parsing string failed".into()),
+        _ => return Ok(ty.clone()),
+    };
+
+    let resolve = |cty: &Type, len_field: &str| -> Result<Box<Type>, Box<EvalAltResult>> {
+        Ok(Box::new(Type::Collection(
+            Box::new(cty.clone()),
+            field_len(n, len_field, buf)?,
+        )))
+    };
+    let mut fields: BTreeMap<u32, Vec<Field>> = BTreeMap::new();
+    for (offset, nf) in n.fields() {
+        let fty = match &nf.ty {
+            Type::DynCollection(cty, len_field) => *resolve(cty, len_field)?,
+            Type::Pointer32(pty) => match &**pty {
+                Type::DynCollection(cty, len_field) => Type::Pointer32(resolve(cty, len_field)?),
+                _ => nf.ty.clone(),
             },
-            Err(e) => Err(e.as_str().into()),
-        },
-        Type::WideString(len) => match mem.read_raw(addr, (len * 2) as usize) {
-            Ok(raw) => {
-                let raw_u16: Vec<u16> = raw
-                    .chunks_exact(2)
-                    .into_iter()
-                    .map(|a| u16::from_ne_bytes([a[0], a[1]]))
-                    .collect();
-                Ok(U16String::from_vec(raw_u16).to_string_lossy().into())
+            Type::Pointer64(pty) => match &**pty {
+                Type::DynCollection(cty, len_field) => Type::Pointer64(resolve(cty, len_field)?),
+                _ => nf.ty.clone(),
+            },
+            Type::Struct(_) if nf.ty.is_dynamic() => {
+                resolve_lengths(&nf.ty, slice(ty, buf, offset, nf.ty.size())?)?
             }
-            Err(e) => Err(e.as_str().into()),
-        },
+            _ => nf.ty.clone(),
+        };
+        fields
+            .entry(offset)
+            .or_default()
+            .push(Field::new(nf.name.clone(), fty));
+    }
+
+    Ok(Type::Struct(Struct::with_align(fields, n.align())))
+}
+
+/// Error for a `DynCollection` outside of a struct, where there is no field to take its length from.
+fn no_length_field(len_field: &str) -> Box<EvalAltResult> {
+    format!(
+        "length of `Collection` is given by the field `{}` of its struct",
+        len_field
+    )
+    .into()
+}
+
+/// Value of the integer field `len_field` of `n` in `buf` as a `Collection` length, within `MAX_COLLECTION_LEN`.
+fn field_len(n: &Struct, len_field: &str, buf: &[u8]) -> Result<u32, Box<EvalAltResult>> {
+    let (offset, lf) = n
+        .fields()
+        .find(|(_, nf)| nf.name == len_field)
+        .ok_or_else(|| format!("no field `{}` for the length of `Collection`", len_field))?;
+    let len: i128 = dyn_to_int(
+        &lf.ty,
+        &decode_dyn(&lf.ty, slice(&lf.ty, buf, offset, lf.ty.size())?)?,
+    )?;
+
+    let max = max_collection_len();
+    match u32::try_from(len) {
+        Ok(len) if len <= max => Ok(len),
+        _ => Err(format!(
+            "length {} of `Collection` from field `{}` is not within 0..={}",
+            len, len_field, max
+        )
+        .into()),
+    }
+}
+
+/// Read `len` bytes at `addr`, erroring if any of them could not be read instead of zero filling them.
+pub fn read_raw_checked(
+    mem: &mut impl MemoryView,
+    addr: Address,
+    len: usize,
+) -> Result<Vec<u8>, Box<EvalAltResult>> {
+    match read_raw_batch(mem, &[(addr, len)])?.pop().flatten() {
+        Some(raw) => Ok(raw),
+        None => Err(format!("failed to read {} bytes at {:#x}", len, addr.to_umem()).into()),
+    }
+}
+
+/// NUL terminated string of at most `max_len` bytes at `addr`, read in small steps so it may end right before
+/// unmapped memory.
+pub fn read_cstr(
+    mem: &mut impl MemoryView,
+    addr: Address,
+    max_len: usize,
+) -> Result<String, Box<EvalAltResult>> {
+    let mut raw = vec![];
+    while raw.len() < max_len {
+        let at = addr + raw.len() as umem;
+        let chunk = read_raw_checked(mem, at, 0x40 - (at.to_umem() % 0x40) as usize)?;
+        match chunk.iter().position(|b| *b == 0) {
+            Some(n) => {
+                raw.extend_from_slice(&chunk[..n]);
+                return Ok(String::from_utf8_lossy(&raw).into_owned());
+            }
+            None => raw.extend_from_slice(&chunk),
+        }
+    }
+    Err(format!("unterminated string at {:#x}", addr.to_umem()).into())
+}
+
+/// Read every `(addr, len)` request with a single batch, requests which could not be entirely read are `None`.
+pub fn read_raw_batch(
+    mem: &mut impl MemoryView,
+    reqs: &[(Address, usize)],
+) -> Result<Vec<Option<Vec<u8>>>, Box<EvalAltResult>> {
+    let mut bufs: Vec<Vec<u8>> = reqs.iter().map(|(_, len)| vec![0; *len]).collect();
+    let mut failed = vec![false; reqs.len()];
+
+    // Failed chunks are matched back to their request through the buffer they were read into.
+    let ranges: Vec<(usize, usize)> = bufs
+        .iter()
+        .map(|buf| (buf.as_ptr() as usize, buf.as_ptr() as usize + buf.len()))
+        .collect();
+    let callback = &mut |CTup2(_, data): ReadData| {
+        let ptr = data.as_ptr() as usize;
+        if let Some(idx) = ranges
+            .iter()
+            .position(|(start, end)| (*start..*end).contains(&ptr))
+        {
+            failed[idx] = true;
+        }
+        true
+    };
+
+    let iter = bufs
+        .iter_mut()
+        .zip(reqs)
+        .map(|(buf, (addr, _))| CTup2(*addr, buf.as_mut_slice().into()));
+    mem.read_iter(iter, None, Some(&mut callback.into()))
+        .map_err(|e| e.as_str())?;
+
+    Ok(bufs
+        .into_iter()
+        .zip(failed)
+        .map(|(buf, failed)| (!failed).then_some(buf))
+        .collect())
+}
+
+/// Largest value in bytes read or encoded at once, so huge types cannot exhaust memory.
+pub const MAX_READ_LEN: usize = 0x1000_0000;
+
+/// Size of `ty` in bytes for reading or encoding it at once, erroring if it overflows or exceeds `MAX_READ_LEN`.
+pub fn byte_len(ty: &Type) -> Result<usize, Box<EvalAltResult>> {
+    match ty.checked_size().map(|size| size as usize) {
+        Some(len) if len <= MAX_READ_LEN => Ok(len),
+        _ => Err(format!(
+            "`{}` is larger than the limit of {:#x} bytes",
+            ty.name(),
+            MAX_READ_LEN
+        )
+        .into()),
+    }
+}
+
+/// Bytes read per batch by `read_pages`.
+pub const CHUNK_SIZE: umem = 0x100000;
+/// Granularity at which `read_pages` skips unreadable memory.
+pub const PAGE_SIZE: umem = 0x1000;
+
+/// Read `size` bytes at `start` in large batches, `f` is called with the address, length and contents of every page
+/// in order, the contents are `None` for pages which could not be read. Reading stops early once `f` returns `false`.
+pub fn read_pages(
+    mem: &mut impl MemoryView,
+    start: Address,
+    size: umem,
+    mut f: impl FnMut(Address, usize, Option<Vec<u8>>) -> Result<bool, Box<EvalAltResult>>,
+) -> Result<(), Box<EvalAltResult>> {
+    let end = start
+        .to_umem()
+        .checked_add(size)
+        .ok_or_else(|| format!("range {:#x} + {:#x} overflowed", start.to_umem(), size))?;
+
+    let mut chunk_start = start.to_umem();
+    while chunk_start < end {
+        let chunk_end = end.min(chunk_start.saturating_add(CHUNK_SIZE));
+
+        let mut pages = vec![];
+        let mut page_start = chunk_start;
+        while page_start < chunk_end {
+            let page_end = chunk_end.min((page_start & !(PAGE_SIZE - 1)) + PAGE_SIZE);
+            pages.push((Address::from(page_start), (page_end - page_start) as usize));
+            page_start = page_end;
+        }
+
+        for ((addr, len), page) in pages.iter().zip(read_raw_batch(mem, &pages)?) {
+            if !f(*addr, *len, page)? {
+                return Ok(());
+            }
+        }
+
+        chunk_start = chunk_end;
+    }
+
+    Ok(())
+}
+
+/// Decode a value of `ty` from the start of `buf`.
+pub fn decode_dyn(ty: &Type, buf: &[u8]) -> Result<Dynamic, Box<EvalAltResult>> {
+    match ty {
+        Type::UInt8 => Ok(Dynamic::from_int(
+            u8::from_ne_bytes(take(ty, buf)?) as rhai::INT
+        )),
+        Type::Int8 => Ok(Dynamic::from_int(
+            i8::from_ne_bytes(take(ty, buf)?) as rhai::INT
+        )),
+        Type::Bool8 => Ok(Dynamic::from_bool(u8::from_ne_bytes(take(ty, buf)?) != 0)),
+        // Single byte characters are treated as latin-1.
+        Type::Char8 => Ok(Dynamic::from_char(u8::from_ne_bytes(take(ty, buf)?).into())),
+        Type::UInt16 => Ok(Dynamic::from_int(
+            u16::from_ne_bytes(take(ty, buf)?) as rhai::INT
+        )),
+        Type::Int16 => Ok(Dynamic::from_int(
+            i16::from_ne_bytes(take(ty, buf)?) as rhai::INT
+        )),
+        // Lone surrogates are replaced, the same as `WideString`.
+        Type::Char16 => Ok(Dynamic::from_char(
+            char::from_u32(u16::from_ne_bytes(take(ty, buf)?).into())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        )),
+        Type::Int32 => Ok(Dynamic::from_int(
+            i32::from_ne_bytes(take(ty, buf)?) as rhai::INT
+        )),
+        Type::UInt32 => Ok(Dynamic::from_int(
+            u32::from_ne_bytes(take(ty, buf)?) as rhai::INT
+        )),
+        Type::Bool32 => Ok(Dynamic::from_bool(u32::from_ne_bytes(take(ty, buf)?) != 0)),
+        Type::Fp32 => Ok(Dynamic::from_float(
+            f32::from_ne_bytes(take(ty, buf)?) as f64
+        )),
+        Type::Address32 => Ok(Dynamic::from(Address::from(u32::from_ne_bytes(take(
+            ty, buf,
+        )?)))),
+        Type::Pointer32(pty) => Ok(Dynamic::from((
+            pty.clone(),
+            Address::from(u32::from_ne_bytes(take(ty, buf)?)),
+        ))),
+        Type::Int64 => Ok(Dynamic::from_int(i64::from_ne_bytes(take(ty, buf)?))),
+        Type::UInt64 => Ok(Dynamic::from(U64(u64::from_ne_bytes(take(ty, buf)?)))),
+        Type::Fp64 => Ok(Dynamic::from_float(f64::from_ne_bytes(take(ty, buf)?))),
+        Type::Address64 => Ok(Dynamic::from(Address::from(u64::from_ne_bytes(take(
+            ty, buf,
+        )?)))),
+        Type::Pointer64(pty) => Ok(Dynamic::from((
+            pty.clone(),
+            Address::from(u64::from_ne_bytes(take(ty, buf)?)),
+        ))),
+        Type::String(len) => {
+            let mut raw = slice(ty, buf, 0, *len)?;
+            // Truncate at the null terminator, if any.
+            if let Some(n) = raw.iter().position(|c| *c == 0) {
+                raw = &raw[..n];
+            }
+            Ok(String::from_utf8_lossy(raw).into_owned().into())
+        }
+        Type::WideString(len) => {
+            let mut raw_u16: Vec<u16> = slice(ty, buf, 0, len * 2)?
+                .chunks_exact(2)
+                .map(|a| u16::from_ne_bytes([a[0], a[1]]))
+                .collect();
+            // Truncate at the null terminator, if any.
+            if let Some(n) = raw_u16.iter().position(|c| *c == 0) {
+                raw_u16.truncate(n);
+            }
+            Ok(U16String::from_vec(raw_u16).to_string_lossy().into())
+        }
         Type::Struct(n) => {
             let mut map = rhai::Map::new();
 
-            for (offset, nf) in n.clone() {
-                // TODO: We are doing seperate read calls for each item, we instead should read up to each padding jump.
-                let field_val = read_to_dyn(mem, &nf.ty, addr + offset)?;
-                map.insert(nf.name.into(), field_val);
+            for (offset, nf) in n.fields() {
+                let field_val = decode_dyn(&nf.ty, slice(ty, buf, offset, nf.ty.size())?)?;
+                map.insert(nf.name.as_str().into(), field_val);
             }
 
             Ok(Dynamic::from_map(map))
         }
-        Type::Collection(ty, num) => {
-            let mut arr = rhai::Array::with_capacity(*num as usize);
-            let size = ty.size();
-
-            let mut current = 0;
-            while current < *num {
-                let item_addr = addr + (current * size);
-                current += 1;
-                // TODO: We are doing seperate read calls for each item, we instead should read the entire list and then iterate inside of it.
-                arr.push(read_to_dyn(mem, ty, item_addr)?);
-            }
+        Type::Collection(cty, num) => {
+            let size = cty.size();
 
-            Ok(Dynamic::from_array(arr))
+            (0..*num)
+                .map(|current| {
+                    let offset = element_pos(ty, current as usize, size)?;
+                    decode_dyn(cty, slice(ty, buf, offset, size)?)
+                })
+                .collect::<Result<rhai::Array, _>>()
+                .map(Dynamic::from_array)
+        }
+        Type::DynCollection(_, len_field) => Err(no_length_field(len_field)),
+        Type::Enum(e) => {
+            let value = dyn_to_int::<i128>(&e.repr, &decode_dyn(&e.repr, buf)?)? as rhai::INT;
+            Ok(match e.flags {
+                true => Dynamic::from_array(e.split_flags(value)),
+                false => Dynamic::from(EnumValue {
+                    ty: e.clone(),
+                    value,
+                }),
+            })
+        }
+        Type::Bitfield(b) => {
+            let raw = storage_bits(ty, buf)?;
+            Ok(Dynamic::from_map(
+                b.bits
+                    .iter()
+                    .map(|bits| (bits.name.as_str().into(), b.extract(bits, raw).into()))
+                    .collect(),
+            ))
         }
     }
 }
 
+/// Storage unit of the bitfield `ty` at the start of `buf`.
+fn storage_bits(ty: &Type, buf: &[u8]) -> Result<u64, Box<EvalAltResult>> {
+    Ok(match ty.size() {
+        1 => u8::from_ne_bytes(take(ty, buf)?).into(),
+        2 => u16::from_ne_bytes(take(ty, buf)?).into(),
+        4 => u32::from_ne_bytes(take(ty, buf)?).into(),
+        _ => u64::from_ne_bytes(take(ty, buf)?),
+    })
+}
+
+/// Byte offset of the element `current` of `size` bytes within the collection `ty`.
+fn element_pos(ty: &Type, current: usize, size: u32) -> Result<u32, Box<EvalAltResult>> {
+    u32::try_from(current)
+        .ok()
+        .and_then(|current| current.checked_mul(size))
+        .ok_or_else(|| {
+            format!(
+                "offset of element {} of `{}` overflowed",
+                current,
+                ty.name()
+            )
+            .into()
+        })
+}
+
+/// Sub-slice of `buf` holding `len` bytes at `offset`.
+fn slice<'a>(
+    ty: &Type,
+    buf: &'a [u8],
+    offset: u32,
+    len: u32,
+) -> Result<&'a [u8], Box<EvalAltResult>> {
+    buf.get(offset as usize..(offset as usize + len as usize))
+        .ok_or_else(|| {
+            format!(
+                "buffer of {} bytes is too small to decode `{}`",
+                buf.len(),
+                ty.name()
+            )
+            .into()
+        })
+}
+
+/// Mutable sub-slice of `buf` holding `len` bytes at `offset`.
+fn slice_mut<'a>(
+    ty: &Type,
+    buf: &'a mut [u8],
+    offset: u32,
+    len: u32,
+) -> Result<&'a mut [u8], Box<EvalAltResult>> {
+    let buf_len = buf.len();
+    buf.get_mut(offset as usize..(offset as usize + len as usize))
+        .ok_or_else(|| {
+            format!(
+                "buffer of {} bytes is too small to encode `{}`",
+                buf_len,
+                ty.name()
+            )
+            .into()
+        })
+}
+
+/// First `N` bytes of `buf`, for decoding primitives.
+fn take<const N: usize>(ty: &Type, buf: &[u8]) -> Result<[u8; N], Box<EvalAltResult>> {
+    Ok(slice(ty, buf, 0, N as u32)?.try_into().unwrap())
+}
+
+/// Write `val` as `ty` at `addr`, structs and collections are written field by field so padding is left untouched.
 pub fn write_from_dyn(
     mem: &mut impl MemoryView,
     ty: &Type,
     addr: Address,
     val: Dynamic,
 ) -> Result<(), Box<EvalAltResult>> {
-    // TODO: Add special logic to write `Address` and other non numerical types.
     match ty {
-        Type::UInt8 => mem
-            .write(addr, &(val.as_int().unwrap() as u8))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::UInt16 => mem
-            .write(addr, &(val.as_int().unwrap() as u16))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::Int32 => mem
-            .write(addr, &(val.as_int().unwrap() as i32))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::UInt32 | Type::Address32 => mem
-            .write(addr, &(val.as_int().unwrap() as u32))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::Fp32 => mem
-            .write(addr, &(val.as_int().unwrap() as f32))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::Pointer32(pty) => match mem.read_addr32(addr) {
+        // Writing an address to a pointer replaces the pointer itself, anything else is written to the pointee.
+        Type::Pointer32(pty) if !is_addr(&val) => match mem.read_addr32(addr) {
             Ok(ptr) => write_from_dyn(mem, pty, ptr, val),
             Err(e) => Err(format!("read pointer to write: {}", e).into()),
         },
-        Type::Int64 => mem
-            .write(addr, &(val.as_int().unwrap() as rhai::INT))
-            .map_err(|e| Box::new(e.as_str().into())),
-        // TODO: u64 -> i64 is very bad if the u64 num sets the sign bit, fix!
-        Type::UInt64 | Type::Address64 => mem
-            .write(addr, &(val.as_int().unwrap() as u64))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::Fp64 => mem
-            .write(addr, &(val.as_int().unwrap() as f64))
-            .map_err(|e| Box::new(e.as_str().into())),
-        Type::Pointer64(pty) => match mem.read_addr64(addr) {
+        Type::Pointer64(pty) if !is_addr(&val) => match mem.read_addr64(addr) {
             Ok(ptr) => write_from_dyn(mem, pty, ptr, val),
             Err(e) => Err(format!("read pointer to write: {}", e).into()),
         },
-        Type::String(len) => match val.into_string() {
-            Ok(str) => {
-                if (*len as usize) < str.len() {
-                    if str.is_ascii() {
-                        mem.write(addr, str.as_bytes())
-                            .map_err(|e| Box::new(e.as_str().into()))
-                    } else {
-                        // i mean we COULD allow writing but this would confuse users (able to write utf8, no utf8 reading)
-                        todo!("return error, not ascii")
-                    }
-                } else {
-                    todo!("return error, string too long")
+        Type::Struct(n) => {
+            let mut map = struct_values(ty, val)?;
+            // Only the given members of a union are written.
+            for (offset, nf) in n.fields() {
+                if let Some(val) = map.remove(nf.name.as_str()) {
+                    write_from_dyn(mem, &nf.ty, addr + offset, val)?;
                 }
             }
-            Err(_) => todo!("return error"),
-        },
-        Type::WideString(len) => match val.into_string() {
-            Ok(str) => {
-                if (*len as usize) < str.len() * 2 {
-                    mem.write(addr, U16String::from_str(&str).as_slice())
-                        .map_err(|e| Box::new(e.as_str().into()))
-                } else {
-                    todo!("return error, widestring too long")
-                }
+
+            Ok(())
+        }
+        // The field holding the length of a `DynCollection` is written like any other field.
+        Type::Collection(cty, _) | Type::DynCollection(cty, _) => {
+            for (current, val) in collection_values(ty, val)?.into_iter().enumerate() {
+                let item_addr = offset_addr(addr, element_offset(cty, current as rhai::INT)?)?;
+                write_from_dyn(mem, cty, item_addr, val)?;
             }
-            Err(_) => todo!("return error"),
-        },
+
+            Ok(())
+        }
+        // Bits which are not written keep their current value.
+        Type::Bitfield(_) => {
+            let mut raw = read_raw_checked(mem, addr, ty.size() as usize)?;
+            encode_dyn(ty, val, &mut raw)?;
+            mem.write_raw(addr, &raw).map_err(|e| e.as_str().into())
+        }
+        _ => {
+            let mut raw = vec![0; byte_len(ty)?];
+            encode_dyn(ty, val, &mut raw)?;
+            mem.write_raw(addr, &raw).map_err(|e| e.as_str().into())
+        }
+    }
+}
+
+/// Encode `val` as `ty` into the start of `buf`, pointers are encoded as their address.
+pub fn encode_dyn(ty: &Type, val: Dynamic, buf: &mut [u8]) -> Result<(), Box<EvalAltResult>> {
+    match ty {
+        Type::UInt8 => put(ty, buf, &dyn_to_int::<u8>(ty, &val)?.to_ne_bytes()),
+        Type::Int8 => put(ty, buf, &dyn_to_int::<i8>(ty, &val)?.to_ne_bytes()),
+        Type::Bool8 => put(ty, buf, &(dyn_to_bool(ty, &val)? as u8).to_ne_bytes()),
+        Type::Char8 => put(ty, buf, &dyn_to_int::<u8>(ty, &val)?.to_ne_bytes()),
+        Type::UInt16 => put(ty, buf, &dyn_to_int::<u16>(ty, &val)?.to_ne_bytes()),
+        Type::Int16 => put(ty, buf, &dyn_to_int::<i16>(ty, &val)?.to_ne_bytes()),
+        Type::Char16 => put(ty, buf, &dyn_to_int::<u16>(ty, &val)?.to_ne_bytes()),
+        Type::Int32 => put(ty, buf, &dyn_to_int::<i32>(ty, &val)?.to_ne_bytes()),
+        Type::UInt32 => put(ty, buf, &dyn_to_int::<u32>(ty, &val)?.to_ne_bytes()),
+        Type::Bool32 => put(ty, buf, &(dyn_to_bool(ty, &val)? as u32).to_ne_bytes()),
+        Type::Fp32 => put(ty, buf, &dyn_to_f32(ty, &val)?.to_ne_bytes()),
+        Type::Address32 | Type::Pointer32(_) => {
+            put(ty, buf, &dyn_to_int::<u32>(ty, &val)?.to_ne_bytes())
+        }
+        Type::Int64 => put(ty, buf, &dyn_to_int::<i64>(ty, &val)?.to_ne_bytes()),
+        Type::UInt64 => put(ty, buf, &dyn_to_int::<u64>(ty, &val)?.to_ne_bytes()),
+        Type::Fp64 => put(ty, buf, &dyn_to_f64(ty, &val)?.to_ne_bytes()),
+        Type::Address64 | Type::Pointer64(_) => {
+            put(ty, buf, &dyn_to_int::<u64>(ty, &val)?.to_ne_bytes())
+        }
+        Type::String(len) => {
+            let str = as_string(ty, val)?;
+            if !str.is_ascii() {
+                // i mean we COULD allow writing but this would confuse users (able to write utf8, no utf8 reading)
+                return Err(format!("cannot write non-ascii string `{}`", str).into());
+            }
+
+            let mut raw = str.as_bytes().to_vec();
+            if raw.len() > *len as usize {
+                return Err(format!(
+                    "string of length {} is too long for `String({})`",
+                    raw.len(),
+                    len
+                )
+                .into());
+            }
+
+            // Pad out the rest of the buffer so shorter strings are terminated.
+            raw.resize(*len as usize, 0);
+            put(ty, buf, &raw)
+        }
+        Type::WideString(len) => {
+            let str = as_string(ty, val)?;
+            let raw = U16String::from_str(&str).into_vec();
+            if raw.len() > *len as usize {
+                return Err(format!(
+                    "string of length {} is too long for `WideString({})`",
+                    raw.len(),
+                    len
+                )
+                .into());
+            }
+
+            // Pad out the rest of the buffer so shorter strings are terminated.
+            let mut raw: Vec<u8> = raw.into_iter().flat_map(u16::to_ne_bytes).collect();
+            raw.resize(ty.size() as usize, 0);
+            put(ty, buf, &raw)
+        }
         Type::Struct(n) => {
-            if let Some(map) = val.try_cast::<rhai::Map>() {
-                // TODO: Wasteful clone due to ref.
-                for (offset, nf) in n.clone() {
-                    match map.get(nf.name.as_str()) {
-                        Some(val) => write_from_dyn(mem, &nf.ty, addr + offset, val.clone())?,
-                        None => todo!("return error"),
-                    }
+            let mut map = struct_values(ty, val)?;
+            // Only the given members of a union are encoded.
+            for (offset, nf) in n.fields() {
+                if let Some(val) = map.remove(nf.name.as_str()) {
+                    encode_dyn(&nf.ty, val, slice_mut(ty, buf, offset, nf.ty.size())?)?;
                 }
-            } else {
-                todo!("return error")
             }
 
             Ok(())
         }
-        Type::Collection(ty, _) => {
-            let arr = val.into_array().unwrap();
-
-            // TODO: Err if vec length mismatch (more or less of what the native type expects)
-            let size = ty.size();
-            arr.into_iter().enumerate().for_each(|(current, val)| {
-                let item_addr = addr + (current as u32 * size);
-                write_from_dyn(mem, ty, item_addr, val).unwrap();
-            });
+        // Elements of a `DynCollection` are not part of its struct's size, so only empty arrays fit.
+        Type::Collection(cty, _) | Type::DynCollection(cty, _) => {
+            let size = cty.size();
+            for (current, val) in collection_values(ty, val)?.into_iter().enumerate() {
+                let offset = element_pos(ty, current, size)?;
+                encode_dyn(cty, val, slice_mut(ty, buf, offset, size)?)?;
+            }
 
             Ok(())
         }
+        // Flags are combined from an array of names and numbers.
+        Type::Enum(e) => {
+            let value = match e.flags && val.is_array() {
+                true => val
+                    .into_array()
+                    .unwrap()
+                    .iter()
+                    .try_fold(0, |acc, val| enum_to_int(e, val).map(|val| acc | val))?,
+                false => enum_to_int(e, &val)?,
+            };
+            // Values of `UInt64` enums above `INT::MAX` wrap around.
+            let val = match *e.repr == Type::UInt64 && value < 0 {
+                true => Dynamic::from(U64(value as u64)),
+                false => Dynamic::from_int(value),
+            };
+            encode_dyn(&e.repr, val, buf)
+        }
+        // Only the bits named in the map are replaced, the rest of `buf` is kept as is.
+        Type::Bitfield(b) => {
+            let mut raw = storage_bits(ty, buf)?;
+            for (name, val) in bitfield_values(ty, val)? {
+                let bits = b
+                    .get(&name)
+                    .ok_or_else(|| format!("no bit field `{}` to write", name))?;
+                raw = b.insert(bits, raw, dyn_to_int::<i128>(ty, &val)?)?;
+            }
+
+            match ty.size() {
+                1 => put(ty, buf, &(raw as u8).to_ne_bytes()),
+                2 => put(ty, buf, &(raw as u16).to_ne_bytes()),
+                4 => put(ty, buf, &(raw as u32).to_ne_bytes()),
+                _ => put(ty, buf, &raw.to_ne_bytes()),
+            }
+        }
+    }
+}
+
+/// Value of the variant name, `EnumValue` or number `val` to write as the enum `e`.
+fn enum_to_int(e: &Enum, val: &Dynamic) -> Result<rhai::INT, Box<EvalAltResult>> {
+    if val.is_string() {
+        let name = val.clone().into_immutable_string()?;
+        e.value_of(&name)
+            .ok_or_else(|| format!("no variant `{}` in `{}`", name, e.name).into())
+    } else {
+        dyn_to_int::<i128>(&e.repr, val).map(|val| val as rhai::INT)
+    }
+}
+
+/// Bits of the map `val` to write as the bitfield `ty`.
+fn bitfield_values(ty: &Type, val: Dynamic) -> Result<rhai::Map, Box<EvalAltResult>> {
+    let type_name = val.type_name();
+    val.try_cast::<rhai::Map>()
+        .ok_or_else(|| format!("cannot write `{}` as `{}`", type_name, ty.name()).into())
+}
+
+/// Fields of the map `val` to write as the struct `ty`, erroring if any are missing.
+///
+/// Fields sharing an offset are members of a union, of which only one has to be present.
+fn struct_values(ty: &Type, val: Dynamic) -> Result<rhai::Map, Box<EvalAltResult>> {
+    let type_name = val.type_name();
+    let map = match val.try_cast::<rhai::Map>() {
+        Some(map) => map,
+        None => return Err(format!("cannot write `{}` as `{}`", type_name, ty.name()).into()),
+    };
+
+    // Make sure every field is present before anything is written.
+    if let Type::Struct(n) = ty {
+        if let Some(fields) =
+            n.0.values()
+                .find(|fields| !fields.iter().any(|nf| map.contains_key(nf.name.as_str())))
+        {
+            let names: Vec<_> = fields.iter().map(|nf| format!("`{}`", nf.name)).collect();
+            return Err(format!("missing field {} to write", names.join(" or ")).into());
+        }
+    }
+
+    Ok(map)
+}
+
+/// Elements of the array `val` to write as the collection `ty`, erroring if the length does not match.
+fn collection_values(ty: &Type, val: Dynamic) -> Result<rhai::Array, Box<EvalAltResult>> {
+    let arr = match val.into_array() {
+        Ok(arr) => arr,
+        Err(type_name) => {
+            return Err(format!("cannot write `{}` as `{}`", type_name, ty.name()).into())
+        }
+    };
+
+    match ty {
+        Type::Collection(_, num) if arr.len() != *num as usize => Err(format!(
+            "array of length {} does not match `Collection` of length {}",
+            arr.len(),
+            num
+        )
+        .into()),
+        Type::DynCollection(_, _) if arr.len() > max_collection_len() as usize => Err(format!(
+            "array of length {} exceeds the `Collection` length limit of {}",
+            arr.len(),
+            max_collection_len()
+        )
+        .into()),
+        _ => Ok(arr),
+    }
+}
+
+/// Copy `raw` into the start of `buf`, for encoding primitives.
+fn put(ty: &Type, buf: &mut [u8], raw: &[u8]) -> Result<(), Box<EvalAltResult>> {
+    slice_mut(ty, buf, 0, raw.len() as u32)?.copy_from_slice(raw);
+    Ok(())
+}
+
+/*
+    Coercion from rhai values to the primitives backing `Type`, used when writing.
+
+    Integers accept `INT`, `U64`, `FLOAT`, `bool`, `char`, `Address` and `NativePointer` values, floats are
+    truncated toward zero (`1.9` -> `1`, `-1.9` -> `-1`). Values that do not fit the target
+    type, along with NaN and infinite floats, are rejected instead of wrapping.
+*/
+
+/// Convert `val` to the integer backing `ty`, erroring if it is out of range.
+pub fn dyn_to_int<T: TryFrom<i128>>(ty: &Type, val: &Dynamic) -> Result<T, Box<EvalAltResult>> {
+    let num: i128 = if let Ok(int) = val.as_int() {
+        int.into()
+    } else if let Ok(fp) = val.as_float() {
+        if !fp.is_finite() {
+            return Err(format!("cannot write `{}` as `{}`", fp, ty.name()).into());
+        }
+        // Saturates at the bounds of `i128`, which are outside of every integer `Type` anyways.
+        fp.trunc() as i128
+    } else if let Ok(b) = val.as_bool() {
+        b.into()
+    } else if let Ok(c) = val.as_char() {
+        u32::from(c).into()
+    } else if val.is::<U64>() {
+        val.clone_cast::<U64>().0.into()
+    } else if val.is::<Address>() {
+        val.clone_cast::<Address>().to_umem().into()
+    } else if val.is::<NativePointer>() {
+        val.clone_cast::<NativePointer>().1.to_umem().into()
+    } else if val.is::<EnumValue>() {
+        val.clone_cast::<EnumValue>().value.into()
+    } else {
+        return Err(format!("cannot write `{}` as `{}`", val.type_name(), ty.name()).into());
+    };
+
+    T::try_from(num)
+        .map_err(|_| format!("value {} is out of range for `{}`", num, ty.name()).into())
+}
+
+/// Convert `val` to the double backing `ty`.
+pub fn dyn_to_f64(ty: &Type, val: &Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    if let Ok(fp) = val.as_float() {
+        Ok(fp)
+    } else if let Ok(int) = val.as_int() {
+        Ok(int as f64)
+    } else if val.is::<U64>() {
+        Ok(val.clone_cast::<U64>().0 as f64)
+    } else {
+        Err(format!("cannot write `{}` as `{}`", val.type_name(), ty.name()).into())
     }
 }
+
+/// Convert `val` to the float backing `ty`, erroring if it is out of range.
+pub fn dyn_to_f32(ty: &Type, val: &Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    let fp = dyn_to_f64(ty, val)?;
+    let fp32 = fp as f32;
+    if fp.is_finite() && !fp32.is_finite() {
+        return Err(format!("value {} is out of range for `{}`", fp, ty.name()).into());
+    }
+    Ok(fp32)
+}
+
+/// Convert `val` to the boolean backing `ty`, any non-zero number is `true`.
+pub fn dyn_to_bool(ty: &Type, val: &Dynamic) -> Result<bool, Box<EvalAltResult>> {
+    match val.as_bool() {
+        Ok(b) => Ok(b),
+        Err(_) => dyn_to_int::<i128>(ty, val).map(|num| num != 0),
+    }
+}
+
+/// Returns `true` if `val` holds an `Address` or `NativePointer`.
+fn is_addr(val: &Dynamic) -> bool {
+    val.is::<Address>() || val.is::<NativePointer>()
+}
+
+fn as_string(ty: &Type, val: Dynamic) -> Result<String, Box<EvalAltResult>> {
+    val.into_string()
+        .map_err(|type_name| format!("cannot write `{}` as `{}`", type_name, ty.name()).into())
+}
@@ -1,4 +1,4 @@
-use std::{any::TypeId, collections::BTreeMap};
+use std::{any::TypeId, collections::BTreeMap, fmt};
 
 use rhai::{plugin::*, EvalContext, Expression};
 
@@ -16,9 +16,21 @@ pub mod export_mod {
 
     // Constructors for 'NativeType' variants
     pub const UInt8: Type = Type::UInt8;
+    /// Signed 8-bit integer.
+    pub const Int8: Type = Type::Int8;
+    /// 8-bit boolean, any non-zero value is `true`.
+    pub const Bool8: Type = Type::Bool8;
+    /// 8-bit latin-1 character.
+    pub const Char8: Type = Type::Char8;
     pub const UInt16: Type = Type::UInt16;
+    /// Signed 16-bit integer.
+    pub const Int16: Type = Type::Int16;
+    /// 16-bit UTF-16 code unit.
+    pub const Char16: Type = Type::Char16;
     pub const Int32: Type = Type::Int32;
     pub const UInt32: Type = Type::UInt32;
+    /// 32-bit boolean (i.e. win32 `BOOL`), any non-zero value is `true`.
+    pub const Bool32: Type = Type::Bool32;
     pub const Fp32: Type = Type::Fp32;
     pub const Address32: Type = Type::Address32;
     pub const Int64: Type = Type::Int64;
@@ -50,26 +62,15 @@ pub mod export_mod {
         Type::Collection(Box::new(ty), size as u32)
     }
 
+    /// Collection whose length is read from the integer field `len_field` of the struct containing it.
+    #[rhai_fn(name = "Collection")]
+    pub fn DynCollection(ty: Type, len_field: &str) -> Type {
+        Type::DynCollection(Box::new(ty), len_field.into())
+    }
+
     #[rhai_fn(pure, global, get = "enum_type")]
     pub fn get_type(native_ty: &mut Type) -> String {
-        match native_ty {
-            Type::UInt8 => "UInt8".to_string(),
-            Type::UInt16 => "UInt16".to_string(),
-            Type::Int32 => "Int32".to_string(),
-            Type::UInt32 => "UInt32".to_string(),
-            Type::Fp32 => "Fp32".to_string(),
-            Type::Address32 => "Address32".to_string(),
-            Type::Pointer32(_) => "Pointer32".to_string(),
-            Type::Int64 => "Int64".to_string(),
-            Type::UInt64 => "UInt64".to_string(),
-            Type::Fp64 => "Fp64".to_string(),
-            Type::Address64 => "Address64".to_string(),
-            Type::Pointer64(_) => "Pointer64".to_string(),
-            Type::String(_) => "String".to_string(),
-            Type::WideString(_) => "WideString".to_string(),
-            Type::Struct(_) => "Struct".to_string(),
-            Type::Collection(_, _) => "Collection".to_string(),
-        }
+        native_ty.name().to_string()
     }
 
     // Access to fields
@@ -78,6 +79,12 @@ pub mod export_mod {
         Dynamic::from_int(native_ty.size().into())
     }
 
+    /// Alignment in bytes the type has within a `native(align)` struct.
+    #[rhai_fn(pure, global, get = "align")]
+    pub fn get_align(native_ty: &mut Type) -> Dynamic {
+        Dynamic::from_int(native_ty.align().into())
+    }
+
     #[rhai_fn(pure, global, get = "native")]
     pub fn get_native_struct(native_ty: &mut Type) -> Dynamic {
         // Clones the native struct and returns it as a custom type.
@@ -87,6 +94,59 @@ pub mod export_mod {
         }
     }
 
+    /// Fields of a `Struct` as maps of `name`, `offset` and `ty` in offset order, `()` for any other type.
+    #[rhai_fn(pure, global, get = "fields")]
+    pub fn get_fields(native_ty: &mut Type) -> Dynamic {
+        match native_ty {
+            Type::Struct(ns) => ns
+                .fields()
+                .map(|(offset, nf)| {
+                    let mut map = rhai::Map::new();
+                    map.insert("name".into(), nf.name.as_str().into());
+                    map.insert("offset".into(), Dynamic::from_int(offset.into()));
+                    map.insert("ty".into(), Dynamic::from(nf.ty.clone()));
+                    Dynamic::from_map(map)
+                })
+                .collect::<rhai::Array>()
+                .into(),
+            _ => Dynamic::UNIT,
+        }
+    }
+
+    /// Layout of a `Bitfield` as maps of `name`, `offset` and `width`, `()` for any other type.
+    #[rhai_fn(pure, global, get = "bits")]
+    pub fn get_bits(native_ty: &mut Type) -> Dynamic {
+        match native_ty {
+            Type::Bitfield(b) => b
+                .bits
+                .iter()
+                .map(|bits| {
+                    let mut map = rhai::Map::new();
+                    map.insert("name".into(), bits.name.as_str().into());
+                    map.insert("offset".into(), Dynamic::from_int(bits.offset.into()));
+                    map.insert("width".into(), Dynamic::from_int(bits.width.into()));
+                    Dynamic::from_map(map)
+                })
+                .collect::<rhai::Array>()
+                .into(),
+            _ => Dynamic::UNIT,
+        }
+    }
+
+    /// Variants of an `Enum` as a map of names to values, `()` for any other type.
+    #[rhai_fn(pure, global, get = "variants")]
+    pub fn get_variants(native_ty: &mut Type) -> Dynamic {
+        match native_ty {
+            Type::Enum(e) => e
+                .variants
+                .iter()
+                .map(|(name, value)| (name.as_str().into(), Dynamic::from_int(*value)))
+                .collect::<rhai::Map>()
+                .into(),
+            _ => Dynamic::UNIT,
+        }
+    }
+
     // Printing
     #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
     pub fn to_string(native_ty: &mut Type) -> String {
@@ -103,14 +163,85 @@ pub mod export_mod {
     pub fn neq(native_ty: &mut Type, native_ty2: Type) -> bool {
         native_ty != &native_ty2
     }
+
+    /// Enum value getters, conversions and comparisons.
+    pub mod enum_value_functions {
+        use super::super::EnumValue;
+
+        /// Name of the variant, `()` if the value has none.
+        #[rhai_fn(pure, global, get = "name")]
+        pub fn get_name(ev: &mut EnumValue) -> Dynamic {
+            ev.name().map_or(Dynamic::UNIT, |name| name.into())
+        }
+
+        /// Numeric value.
+        #[rhai_fn(pure, global, get = "value", name = "to_int")]
+        pub fn get_value(ev: &mut EnumValue) -> rhai::INT {
+            ev.value
+        }
+
+        /// Display the variant name, or the enum name and value if the value has none.
+        #[rhai_fn(pure, global, name = "to_string")]
+        pub fn to_string(ev: &mut EnumValue) -> String {
+            ev.to_string()
+        }
+
+        /// Display the enum name, variant and value.
+        #[rhai_fn(pure, global, name = "to_debug")]
+        pub fn to_debug(ev: &mut EnumValue) -> String {
+            format!("{}::{}({})", ev.ty.name, ev.name().unwrap_or("?"), ev.value)
+        }
+
+        /// Return `true` if both values are equal.
+        #[rhai_fn(pure, global, name = "==")]
+        pub fn eq(ev: &mut EnumValue, ev2: EnumValue) -> bool {
+            ev.value == ev2.value
+        }
+
+        /// Return `true` if the value is the variant `name`.
+        #[rhai_fn(pure, global, name = "==")]
+        pub fn eq_name(ev: &mut EnumValue, name: &str) -> bool {
+            ev.name() == Some(name)
+        }
+
+        /// Return `true` if the value equals `num`.
+        #[rhai_fn(pure, global, name = "==")]
+        pub fn eq_num(ev: &mut EnumValue, num: rhai::INT) -> bool {
+            ev.value == num
+        }
+
+        /// Return `true` if both values are not equal.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq(ev: &mut EnumValue, ev2: EnumValue) -> bool {
+            ev.value != ev2.value
+        }
+
+        /// Return `true` if the value is not the variant `name`.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq_name(ev: &mut EnumValue, name: &str) -> bool {
+            ev.name() != Some(name)
+        }
+
+        /// Return `true` if the value does not equal `num`.
+        #[rhai_fn(pure, global, name = "!=")]
+        pub fn neq_num(ev: &mut EnumValue, num: rhai::INT) -> bool {
+            ev.value != num
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Type {
     UInt8,
+    Int8,
+    Bool8,
+    Char8,
     UInt16,
+    Int16,
+    Char16,
     Int32,
     UInt32,
+    Bool32,
     Fp32,
     Address32,
     Pointer32(Box<Type>),
@@ -124,19 +255,338 @@ pub enum Type {
     WideString(u32),
     Struct(Struct),
     Collection(Box<Type>, u32),
+    /// Collection whose length is given by a sibling field, resolved when its struct is read.
+    DynCollection(Box<Type>, String),
+    Bitfield(Bitfield),
+    Enum(Enum),
 }
 
 impl Type {
-    /// Size in bytes
-    pub fn size(&self) -> u32 {
+    /// Name of the variant, as exported to rhai.
+    pub fn name(&self) -> &'static str {
         match self {
-            Self::UInt8 => 1,
-            Self::UInt16 => 2,
-            Self::Int32 | Self::UInt32 | Self::Fp32 | Self::Address32 | Self::Pointer32(_) => 4,
+            Self::UInt8 => "UInt8",
+            Self::Int8 => "Int8",
+            Self::Bool8 => "Bool8",
+            Self::Char8 => "Char8",
+            Self::UInt16 => "UInt16",
+            Self::Int16 => "Int16",
+            Self::Char16 => "Char16",
+            Self::Int32 => "Int32",
+            Self::UInt32 => "UInt32",
+            Self::Bool32 => "Bool32",
+            Self::Fp32 => "Fp32",
+            Self::Address32 => "Address32",
+            Self::Pointer32(_) => "Pointer32",
+            Self::Int64 => "Int64",
+            Self::UInt64 => "UInt64",
+            Self::Fp64 => "Fp64",
+            Self::Address64 => "Address64",
+            Self::Pointer64(_) => "Pointer64",
+            Self::String(_) => "String",
+            Self::WideString(_) => "WideString",
+            Self::Struct(_) => "Struct",
+            Self::Collection(_, _) | Self::DynCollection(_, _) => "Collection",
+            Self::Bitfield(_) => "Bitfield",
+            Self::Enum(_) => "Enum",
+        }
+    }
+
+    /// Size in bytes, saturating at `u32::MAX` for types whose size does not fit.
+    pub fn size(&self) -> u32 {
+        self.checked_size().unwrap_or(u32::MAX)
+    }
+
+    /// Size in bytes, `None` if it does not fit into a `u32`.
+    pub fn checked_size(&self) -> Option<u32> {
+        Some(match self {
+            Self::UInt8 | Self::Int8 | Self::Bool8 | Self::Char8 => 1,
+            Self::UInt16 | Self::Int16 | Self::Char16 => 2,
+            Self::Int32
+            | Self::UInt32
+            | Self::Bool32
+            | Self::Fp32
+            | Self::Address32
+            | Self::Pointer32(_) => 4,
             Self::Int64 | Self::UInt64 | Self::Fp64 | Self::Address64 | Self::Pointer64(_) => 8,
-            Self::String(len) | Type::WideString(len) => *len,
-            Self::Struct(u) => u.size(),
-            Self::Collection(u, size) => size * u.size(),
+            Self::String(len) => *len,
+            Self::WideString(len) => len.checked_mul(2)?,
+            Self::Struct(u) => u.checked_size()?,
+            Self::Collection(u, size) => size.checked_mul(u.checked_size()?)?,
+            // Like a flexible array member, the elements are not part of the struct's size.
+            Self::DynCollection(_, _) => 0,
+            Self::Bitfield(b) => b.storage.size(),
+            Self::Enum(e) => e.repr.size(),
+        })
+    }
+
+    /// Natural alignment of the type following C/MSVC rules, where structs have the alignment they were defined with.
+    pub fn align(&self) -> u32 {
+        match self {
+            Self::String(_) => 1,
+            Self::WideString(_) => 2,
+            Self::Struct(u) => u.align(),
+            Self::Collection(u, _) | Self::DynCollection(u, _) => u.align(),
+            Self::Bitfield(b) => b.storage.align(),
+            Self::Enum(e) => e.repr.align(),
+            _ => self.size(),
+        }
+    }
+
+    /// Whether the length of the type or one of its pointees depends on a field of a struct, i.e. `DynCollection`.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Self::DynCollection(_, _) => true,
+            Self::Pointer32(pty) | Self::Pointer64(pty) => {
+                matches!(**pty, Self::DynCollection(_, _))
+            }
+            Self::Struct(u) => u.fields().any(|(_, nf)| nf.ty.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    /// Whether the type is a signed integer.
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Self::Int8 | Self::Int16 | Self::Int32 | Self::Int64)
+    }
+
+    /// Smallest and largest value of an integer type, `None` for any other type.
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        match self {
+            Self::UInt8 => Some((u8::MIN.into(), u8::MAX.into())),
+            Self::Int8 => Some((i8::MIN.into(), i8::MAX.into())),
+            Self::UInt16 => Some((u16::MIN.into(), u16::MAX.into())),
+            Self::Int16 => Some((i16::MIN.into(), i16::MAX.into())),
+            Self::UInt32 => Some((u32::MIN.into(), u32::MAX.into())),
+            Self::Int32 => Some((i32::MIN.into(), i32::MAX.into())),
+            Self::UInt64 => Some((u64::MIN.into(), u64::MAX.into())),
+            Self::Int64 => Some((i64::MIN.into(), i64::MAX.into())),
+            _ => None,
+        }
+    }
+}
+
+/// Named run of bits within the storage unit of a `Bitfield`, `offset` counts from the least significant bit.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bits {
+    pub name: String,
+    pub offset: u32,
+    pub width: u32,
+}
+
+impl Bits {
+    pub fn mask(&self) -> u64 {
+        (u64::MAX >> (64 - self.width)) << self.offset
+    }
+}
+
+/// Bits packed into a single integer storage unit (i.e. `ULONG Flag1 : 1` in C), read back as a map of the named bits.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bitfield {
+    pub storage: Box<Type>,
+    pub bits: Vec<Bits>,
+}
+
+impl Bitfield {
+    /// Lay out `fields` of `(name, width)` from the least significant bit up, fields without a name are skipped bits.
+    pub fn new(
+        storage: Type,
+        fields: Vec<(Option<String>, u32)>,
+    ) -> Result<Self, Box<EvalAltResult>> {
+        if storage.int_range().is_none() {
+            return Err(format!(
+                "bitfield storage must be an integer type, not `{}`",
+                storage.name()
+            )
+            .into());
+        }
+
+        let mut bits: Vec<Bits> = vec![];
+        let mut offset = 0;
+        for (name, width) in fields {
+            if width == 0 {
+                return Err(format!(
+                    "bit field `{}` must be at least 1 bit wide",
+                    name.as_deref().unwrap_or("_")
+                )
+                .into());
+            }
+            if let Some(name) = name {
+                if bits.iter().any(|b| b.name == name) {
+                    return Err(format!("duplicate bit field `{}`", name).into());
+                }
+                bits.push(Bits {
+                    name,
+                    offset,
+                    width,
+                });
+            }
+            offset = offset.saturating_add(width);
+        }
+
+        if offset > storage.size() * 8 {
+            return Err(format!(
+                "bit fields of {} bits do not fit in `{}`",
+                offset,
+                storage.name()
+            )
+            .into());
+        }
+
+        Ok(Self {
+            storage: Box::new(storage),
+            bits,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bits> {
+        self.bits.iter().find(|b| b.name == name)
+    }
+
+    /// Value of `bits` in the storage unit `raw`, sign extended if the storage is signed.
+    pub fn extract(&self, bits: &Bits, raw: u64) -> rhai::INT {
+        let val = (raw & bits.mask()) >> bits.offset;
+        match self.storage.is_signed() {
+            true => ((val << (64 - bits.width)) as i64) >> (64 - bits.width),
+            false => val as rhai::INT,
+        }
+    }
+
+    /// Storage unit `raw` with `bits` replaced by `val`, erroring if `val` does not fit.
+    pub fn insert(&self, bits: &Bits, raw: u64, val: i128) -> Result<u64, Box<EvalAltResult>> {
+        let (min, max) = match self.storage.is_signed() {
+            true => (
+                -(1i128 << (bits.width - 1)),
+                (1i128 << (bits.width - 1)) - 1,
+            ),
+            false => (0, (1i128 << bits.width) - 1),
+        };
+        if val < min || val > max {
+            return Err(format!(
+                "value {} is out of range for bit field `{}` of {} bits",
+                val, bits.name, bits.width
+            )
+            .into());
+        }
+        Ok((raw & !bits.mask()) | (((val as u64) << bits.offset) & bits.mask()))
+    }
+}
+
+/// Integer type with named values (`native enum`), or with named bits which combine (`native flags`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub repr: Box<Type>,
+    pub variants: Vec<(String, rhai::INT)>,
+    pub flags: bool,
+}
+
+impl Enum {
+    /// Variants without a value follow the previous one, the next number for enums and the next bit for flags.
+    pub fn new(
+        name: String,
+        repr: Type,
+        variants: Vec<(String, Option<rhai::INT>)>,
+        flags: bool,
+    ) -> Result<Self, Box<EvalAltResult>> {
+        let (min, max) = repr.int_range().ok_or_else(|| {
+            format!(
+                "`{}` must be backed by an integer type, not `{}`",
+                name,
+                repr.name()
+            )
+        })?;
+
+        let mut values: Vec<(String, rhai::INT)> = vec![];
+        for (variant, value) in variants {
+            if values.iter().any(|(v, _)| *v == variant) {
+                return Err(format!("duplicate variant `{}` in `{}`", variant, name).into());
+            }
+            let value = match (value, values.last()) {
+                (Some(value), _) => value,
+                (None, None) => flags as rhai::INT,
+                (None, Some((_, prev))) if flags => match prev {
+                    0 => 1,
+                    prev => (1 as rhai::INT)
+                        .checked_shl(64 - prev.leading_zeros())
+                        .ok_or_else(|| format!("no next flag bit after `{}`", prev))?,
+                },
+                (None, Some((_, prev))) => prev.wrapping_add(1),
+            };
+            if !(min..=max).contains(&value.into()) {
+                return Err(format!(
+                    "value {} of `{}` is out of range for `{}`",
+                    value,
+                    variant,
+                    repr.name()
+                )
+                .into());
+            }
+            values.push((variant, value));
+        }
+
+        Ok(Self {
+            name,
+            repr: Box::new(repr),
+            variants: values,
+            flags,
+        })
+    }
+
+    /// Value of the variant `name`.
+    pub fn value_of(&self, name: &str) -> Option<rhai::INT> {
+        self.variants
+            .iter()
+            .find_map(|(v, value)| (v == name).then_some(*value))
+    }
+
+    /// Name of the first variant with `value`.
+    pub fn name_of(&self, value: rhai::INT) -> Option<&str> {
+        self.variants
+            .iter()
+            .find_map(|(v, val)| (*val == value).then_some(v.as_str()))
+    }
+
+    /// Names of the flags set in `value`, bits not covered by any flag are appended as a number.
+    pub fn split_flags(&self, value: rhai::INT) -> rhai::Array {
+        let mut covered = 0;
+        let mut set: rhai::Array = vec![];
+        for (name, flag) in &self.variants {
+            // A zero flag (i.e. `None = 0`) is only set when nothing else is.
+            let is_set = match flag {
+                0 => value == 0,
+                flag => value & flag == *flag,
+            };
+            if is_set {
+                set.push(name.as_str().into());
+                covered |= flag;
+            }
+        }
+        if value & !covered != 0 {
+            set.push(Dynamic::from_int(value & !covered));
+        }
+        set
+    }
+}
+
+/// Value read as an `Enum`, discriminants without a variant are kept as is so they can be written back.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EnumValue {
+    pub ty: Enum,
+    pub value: rhai::INT,
+}
+
+impl EnumValue {
+    pub fn name(&self) -> Option<&str> {
+        self.ty.name_of(self.value)
+    }
+}
+
+impl fmt::Display for EnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}({})", self.ty.name, self.value),
         }
     }
 }
@@ -153,29 +603,59 @@ impl Field {
     }
 }
 
+/// Fields by offset, where union members and explicitly overlapping fields share or cross offsets, and the struct's
+/// alignment, which is `1` for packed structs.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Struct(pub BTreeMap<u32, Field>);
+pub struct Struct(pub BTreeMap<u32, Vec<Field>>, pub u32);
 
 impl Struct {
-    pub fn new(fields: BTreeMap<u32, Field>) -> Self {
-        Self(fields)
+    /// A packed struct.
+    pub fn new(fields: BTreeMap<u32, Vec<Field>>) -> Self {
+        Self(fields, 1)
+    }
+
+    /// A struct whose size is padded to a multiple of `align`.
+    pub fn with_align(fields: BTreeMap<u32, Vec<Field>>, align: u32) -> Self {
+        Self(fields, align)
     }
 
+    /// Size in bytes, saturating at `u32::MAX` for structs whose size does not fit.
     pub fn size(&self) -> u32 {
-        match self.0.last_key_value() {
-            // Adds the last offset + the fields type size to get the max size of the `NativeType::User`
-            Some(last_field) => last_field.0 + last_field.1.ty.size(),
-            None => 0,
-        }
+        self.checked_size().unwrap_or(u32::MAX)
     }
 
-    pub fn get_field(&self, offset: u32) -> Option<&Field> {
-        self.0.get(&offset)
+    /// Size in bytes, `None` if it does not fit into a `u32`.
+    pub fn checked_size(&self) -> Option<u32> {
+        // The largest field end, since overlapping fields may reach past the last offset, plus tail padding.
+        self.fields()
+            .map(|(offset, nf)| offset.checked_add(nf.ty.checked_size()?))
+            .try_fold(0, |end, field_end| Some(end.max(field_end?)))?
+            .checked_next_multiple_of(self.1.max(1))
     }
 
-    pub fn get_field_from_name(&self, field_name: &str) -> Option<&Field> {
+    pub fn align(&self) -> u32 {
+        self.1
+    }
+
+    /// Every field with its offset, in offset order and then declaration order.
+    pub fn fields(&self) -> impl Iterator<Item = (u32, &Field)> {
         self.0
             .iter()
+            .flat_map(|(offset, fields)| fields.iter().map(move |nf| (*offset, nf)))
+    }
+
+    /// Add a field at `offset`, after any fields already there.
+    pub fn insert(&mut self, offset: u32, field: Field) {
+        self.0.entry(offset).or_default().push(field);
+    }
+
+    /// The first field declared at `offset`.
+    pub fn get_field(&self, offset: u32) -> Option<&Field> {
+        self.0.get(&offset).and_then(|fields| fields.first())
+    }
+
+    pub fn get_field_from_name(&self, field_name: &str) -> Option<&Field> {
+        self.fields()
             .find_map(|(_, nf)| field_name.eq(&nf.name).then_some(nf))
     }
 }
@@ -183,81 +663,540 @@ impl Struct {
 impl IntoIterator for Struct {
     type Item = (u32, Field);
 
-    type IntoIter = std::collections::btree_map::IntoIter<u32, Field>;
+    type IntoIter = std::vec::IntoIter<(u32, Field)>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.0
+            .into_iter()
+            .flat_map(|(offset, fields)| fields.into_iter().map(move |nf| (offset, nf)))
+            .collect::<Vec<_>>()
+            .into_iter()
     }
 }
 
+/// Position within a `native` block, derived from the symbols parsed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NativeState {
+    /// After `native`, expecting attributes, `enum`, `flags` or the struct name.
+    Header,
+    /// Start of an attribute within `(...)`.
+    Attr,
+    /// After an attribute name, which may be followed by `=` and its value.
+    AfterAttr,
+    /// After `=`, expecting the attribute value.
+    AttrValue,
+    /// After a complete attribute.
+    AttrEnd,
+    /// After the attributes, expecting the struct name.
+    Name,
+    /// After the struct name, expecting `{`.
+    Open,
+    /// Start of a field, padding (`^`), an explicit offset (`@`), a `union` or a named field.
+    Field,
+    /// After `^`, expecting the padding size.
+    Padding,
+    /// After `@`, expecting the field offset.
+    At,
+    /// After `union`, expecting `{`.
+    Union,
+    /// After a field name, expecting `:`.
+    Colon,
+    /// After `:`, expecting the field type.
+    Type,
+    /// After a field type, which may be followed by a bit field group.
+    AfterType,
+    /// After a complete field.
+    FieldEnd,
+    /// Start of a bit field, a name or `_` for skipped bits.
+    Bits,
+    /// After a bit field name, expecting `:`.
+    BitsColon,
+    /// After `:`, expecting the bit width.
+    BitsWidth,
+    /// After a complete bit field.
+    BitsEnd,
+    /// After `enum` or `flags`, expecting the enum name.
+    EnumName,
+    /// After the enum name, expecting `:`.
+    EnumColon,
+    /// After `:`, expecting the enum representation.
+    EnumRepr,
+    /// After the enum representation, expecting `{`.
+    EnumOpen,
+    /// Start of an enum variant.
+    Variant,
+    /// After a variant name, which may be followed by `=` and its value.
+    AfterVariant,
+    /// After `=`, expecting the variant value.
+    Value,
+    /// After a complete variant.
+    VariantEnd,
+    /// After the closing `}` of the block.
+    Done,
+}
+
 fn parse_native(
     symbols: &[ImmutableString],
     look_ahead: &str,
 ) -> Result<Option<ImmutableString>, rhai::ParseError> {
-    match (symbols.len(), look_ahead) {
-        (1, _) => Ok(Some("$ident$".into())),
-        (2, _) => Ok(Some("{".into())),
-        (x, lh) if x >= 2 => {
-            // Get the previously parsed field symbols.
-            let mut field_symbols: Vec<&ImmutableString> = symbols
-                .iter()
-                .rev()
-                .take_while(|s| **s != "{" && **s != ",")
-                .collect();
-
-            // Fix direction to get: <keyword> <param1> <param2>.
-            field_symbols.reverse();
-
-            // TODO: The below code hardcodes the different field types, regular and padding (denoted by `^`), if we ever want to add more it might be a good idea to transform the symbols into an enum and match against that instead.
-
-            let res = match field_symbols.first() {
-                Some(kw) => {
-                    let keyword = kw.as_str();
-                    match (field_symbols.len(), lh) {
-                        (1, _) => match keyword {
-                            "^" => Some("$int$"),
-                            _ => Some(":"),
-                        },
-                        (2, _) => match keyword {
-                            "^" => None,
-                            _ => Some("$expr$"),
-                        },
-                        _ => None,
+    // Nested braces (bit field groups and unions) rule out looking back to the last `{`, so replay every symbol.
+    // Symbols which `implement_native` needs to see are parsed as `$symbol$` or `$token$` so they show up in its
+    // inputs, such as the braces of a group, `enum` and `@`.
+    let (state, in_union) =
+        symbols[1..]
+            .iter()
+            .fold((NativeState::Header, false), |(state, in_union), symbol| {
+                let state = match (state, symbol.as_str()) {
+                    (NativeState::Header, "(") => NativeState::Attr,
+                    (NativeState::Header, "enum" | "flags") => NativeState::EnumName,
+                    (NativeState::Header | NativeState::Name, _) => NativeState::Open,
+                    (NativeState::Attr, _) => NativeState::AfterAttr,
+                    (NativeState::AfterAttr, "=") => NativeState::AttrValue,
+                    (NativeState::AttrValue, _) => NativeState::AttrEnd,
+                    (NativeState::AfterAttr | NativeState::AttrEnd, ")") => NativeState::Name,
+                    (NativeState::AfterAttr | NativeState::AttrEnd, _) => NativeState::Attr,
+                    (NativeState::Open, _) => NativeState::Field,
+                    (NativeState::Field, "^") => NativeState::Padding,
+                    (NativeState::Field, "@") => NativeState::At,
+                    (NativeState::Field, "union") => NativeState::Union,
+                    (NativeState::Field, _) => NativeState::Colon,
+                    (NativeState::Padding, _) => NativeState::FieldEnd,
+                    (NativeState::At | NativeState::Union, _) => NativeState::Field,
+                    (NativeState::Colon, _) => NativeState::Type,
+                    (NativeState::Type, _) => NativeState::AfterType,
+                    (NativeState::AfterType, "{") => NativeState::Bits,
+                    (NativeState::AfterType | NativeState::FieldEnd, "}") if in_union => {
+                        return (NativeState::FieldEnd, false)
+                    }
+                    (NativeState::AfterType | NativeState::FieldEnd, "}") => NativeState::Done,
+                    (NativeState::AfterType | NativeState::FieldEnd, _) => NativeState::Field,
+                    (NativeState::Bits, _) => NativeState::BitsColon,
+                    (NativeState::BitsColon, _) => NativeState::BitsWidth,
+                    (NativeState::BitsWidth, _) => NativeState::BitsEnd,
+                    (NativeState::BitsEnd, "}") => NativeState::FieldEnd,
+                    (NativeState::BitsEnd, _) => NativeState::Bits,
+                    (NativeState::EnumName, _) => NativeState::EnumColon,
+                    (NativeState::EnumColon, _) => NativeState::EnumRepr,
+                    (NativeState::EnumRepr, _) => NativeState::EnumOpen,
+                    (NativeState::EnumOpen, _) => NativeState::Variant,
+                    (NativeState::Variant, _) => NativeState::AfterVariant,
+                    (NativeState::AfterVariant, "=") => NativeState::Value,
+                    (NativeState::Value, _) => NativeState::VariantEnd,
+                    (NativeState::AfterVariant | NativeState::VariantEnd, "}") => NativeState::Done,
+                    (NativeState::AfterVariant | NativeState::VariantEnd, _) => {
+                        NativeState::Variant
                     }
+                    (NativeState::Done, _) => NativeState::Done,
+                };
+                (state, in_union || state == NativeState::Union)
+            });
+
+    let expected = match (state, look_ahead) {
+        (NativeState::Header, "(") => "$symbol$",
+        (NativeState::Header, "enum" | "flags") => "$token$",
+        (NativeState::Header | NativeState::Name, _) => "$ident$",
+        (NativeState::Attr, _) => "$ident$",
+        (NativeState::AfterAttr | NativeState::AfterVariant, "=") => "$token$",
+        (NativeState::AttrValue | NativeState::Value, _) => "$expr$",
+        (NativeState::AfterAttr | NativeState::AttrEnd, ")") => "$symbol$",
+        (NativeState::AfterAttr | NativeState::AttrEnd, _) => ",",
+        (NativeState::Open | NativeState::EnumOpen, _) => "{",
+        // Unions only hold named fields.
+        (NativeState::Field, _) if in_union => "$ident$",
+        (NativeState::Field, "^" | "@") => "$symbol$",
+        (NativeState::Field, "union") => "$token$",
+        (NativeState::Field, _) => "$ident$",
+        (NativeState::Padding | NativeState::At | NativeState::BitsWidth, _) => "$int$",
+        (NativeState::Union, _) => "$symbol$",
+        (NativeState::Colon | NativeState::BitsColon | NativeState::EnumColon, _) => ":",
+        (NativeState::Type | NativeState::EnumRepr, _) => "$expr$",
+        (NativeState::AfterType, "{") => "$symbol$",
+        (NativeState::AfterType | NativeState::FieldEnd, "}") if in_union => "$symbol$",
+        (NativeState::AfterType | NativeState::FieldEnd, "}") => "}",
+        (NativeState::AfterType | NativeState::FieldEnd, _) => ",",
+        // `_` is matched as a keyword so skipped bits show up as a width without a name.
+        (NativeState::Bits, "_") => "_",
+        (NativeState::Bits, _) => "$ident$",
+        (NativeState::BitsEnd, "}") => "$symbol$",
+        (NativeState::BitsEnd, _) => ",",
+        (NativeState::EnumName | NativeState::Variant, _) => "$ident$",
+        (NativeState::AfterVariant | NativeState::VariantEnd, "}") => "}",
+        (NativeState::AfterVariant | NativeState::VariantEnd, _) => ",",
+        (NativeState::Done, _) => return Ok(None),
+    };
+    Ok(Some(expected.into()))
+}
+
+/// Parse the bit fields of a group up to its closing `}`, i.e. `alive: 1, team: 3, _: 4 }`.
+///
+/// `_` is matched as a keyword so skipped bits show up as a width without a name.
+fn parse_bits<'a, 'e: 'a>(
+    exprs: &mut impl Iterator<Item = &'a Expression<'e>>,
+) -> Result<Vec<(Option<String>, u32)>, Box<EvalAltResult>> {
+    let mut fields = vec![];
+    loop {
+        let expr = exprs.next().ok_or("unterminated bit field group")?;
+        let (name, width) = match expr.get_string_value() {
+            Some("}") => return Ok(fields),
+            Some(name) => (Some(name.to_string()), exprs.next()),
+            None => (None, Some(expr)),
+        };
+        let width = width
+            .and_then(|expr| expr.get_literal_value::<rhai::INT>())
+            .and_then(|width| u32::try_from(width).ok())
+            .ok_or("bit field width must be a non-negative constant literal")?;
+        fields.push((name, width));
+    }
+}
+
+/// Define an enum or flags type, i.e. `native enum Team : UInt8 { Red = 1, Blue }`.
+fn implement_enum(
+    context: &mut EvalContext,
+    inputs: &[Expression],
+    flags: bool,
+) -> Result<Dynamic, Box<EvalAltResult>> {
+    let enum_name = inputs[1].get_string_value().unwrap();
+    let repr = context.eval_expression_tree(&inputs[2])?;
+    let repr = match repr.clone().try_cast::<Type>() {
+        Some(repr) => repr,
+        None => {
+            return Err(format!(
+                "`{}` must be backed by an integer type, not `{}`",
+                enum_name,
+                repr.type_name()
+            )
+            .into())
+        }
+    };
+
+    let mut variants = vec![];
+    let mut expr_iter = inputs[3..].iter().peekable();
+    while let Some(expr) = expr_iter.next() {
+        let variant = expr.get_string_value().unwrap().to_string();
+        let value = match expr_iter.peek().and_then(|expr| expr.get_string_value()) {
+            Some("=") => {
+                expr_iter.next();
+                let value = context.eval_expression_tree(expr_iter.next().unwrap())?;
+                Some(value.as_int().map_err(|ty| {
+                    format!("value of `{}` must be an integer, not `{}`", variant, ty)
+                })?)
+            }
+            _ => None,
+        };
+        variants.push((variant, value));
+    }
+
+    let native = Enum::new(enum_name.to_string(), repr, variants, flags)?;
+    context
+        .scope_mut()
+        .push_constant(enum_name, Type::Enum(native));
+
+    Ok(Dynamic::UNIT)
+}
+
+/// Field placement within a `native` block, checking that fields only overlap where allowed.
+struct Layout {
+    fields: BTreeMap<u32, Vec<Field>>,
+    /// Offset of the next field.
+    offset: u32,
+    /// Whether fields may overlap outside of unions, set by `native(overlap)`.
+    overlap: bool,
+    /// Largest alignment of any field, `1` for packed structs and unlimited for `native(align)`.
+    pack: u32,
+    /// Alignment of the struct, which is the largest alignment of its fields.
+    align: u32,
+    /// Whether the next field was given an explicit offset, which is used as is.
+    explicit: bool,
+    /// Members of the union being declared, placed once its closing `}` is reached.
+    union: Option<Vec<(String, Type)>>,
+    /// Number of unions seen so far, identifying their members.
+    unions: usize,
+    /// Placed fields as their start, end, name and union.
+    placed: Vec<(u32, u32, String, Option<usize>)>,
+}
+
+impl Layout {
+    fn new(attrs: Attributes) -> Self {
+        Self {
+            fields: BTreeMap::new(),
+            offset: 0,
+            overlap: attrs.overlap,
+            pack: attrs.pack,
+            align: 1,
+            explicit: false,
+            union: None,
+            unions: 0,
+            placed: vec![],
+        }
+    }
+
+    /// Alignment of `ty` as a field, limited by the packing.
+    fn field_align(&self, ty: &Type) -> u32 {
+        ty.align().clamp(1, self.pack)
+    }
+
+    /// Move to the next field offset aligned to `align`, unless an explicit offset was given.
+    fn align_to(&mut self, align: u32) -> Result<(), Box<EvalAltResult>> {
+        if !std::mem::take(&mut self.explicit) {
+            self.offset = self.offset.checked_next_multiple_of(align).ok_or_else(|| {
+                format!(
+                    "offset {:#x} aligned to {} is out of range",
+                    self.offset, align
+                )
+            })?;
+        }
+        self.align = self.align.max(align);
+        Ok(())
+    }
+
+    /// Move the offset of the next field forward by `len` bytes.
+    fn advance(&mut self, len: u32) -> Result<(), Box<EvalAltResult>> {
+        self.offset = self
+            .offset
+            .checked_add(len)
+            .ok_or_else(|| format!("offset {:#x} + {:#x} is out of range", self.offset, len))?;
+        Ok(())
+    }
+
+    /// Set the offset of the next field.
+    fn seek(&mut self, offset: u32) {
+        self.offset = offset;
+        self.explicit = true;
+    }
+
+    /// Place a field at the next aligned offset, or add it to the current union.
+    fn place(&mut self, name: &str, ty: Type) -> Result<(), Box<EvalAltResult>> {
+        if let Some(members) = &mut self.union {
+            members.push((name.to_string(), ty));
+            return Ok(());
+        }
+
+        self.align_to(self.field_align(&ty))?;
+        let size = ty.size();
+        self.put(self.offset, name, ty, None)?;
+        self.advance(size)
+    }
+
+    /// Insert a field at `start`, erroring if it overlaps a field outside of its union.
+    fn put(
+        &mut self,
+        start: u32,
+        name: &str,
+        ty: Type,
+        group: Option<usize>,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let end = start
+            .checked_add(ty.size())
+            .ok_or_else(|| format!("offset {:#x} of field `{}` is out of range", start, name))?;
+        // Zero-sized fields do not occupy any bytes to conflict over.
+        if !self.overlap && start < end {
+            if let Some((other_start, _, other, _)) = self.placed.iter().find(|other| {
+                other.0 < end && start < other.1 && (group.is_none() || other.3 != group)
+            }) {
+                return Err(format!(
+                    "field `{}` at {:#x} overlaps field `{}` at {:#x}",
+                    name, start, other, other_start
+                )
+                .into());
+            }
+        }
+
+        self.placed.push((start, end, name.to_string(), group));
+        self.fields
+            .entry(start)
+            .or_default()
+            .push(Field::new(name.to_string(), ty));
+        Ok(())
+    }
+
+    fn open_union(&mut self) {
+        self.union = Some(vec![]);
+    }
+
+    /// Place the members of the current union at a shared offset aligned for all of them, moving past the largest.
+    fn close_union(&mut self) -> Result<(), Box<EvalAltResult>> {
+        let members = self.union.take().unwrap_or_default();
+        let align = members
+            .iter()
+            .map(|(_, ty)| self.field_align(ty))
+            .max()
+            .unwrap_or(1);
+        self.align_to(align)?;
+
+        self.unions += 1;
+        let mut size = 0;
+        for (name, ty) in members {
+            size = size.max(ty.size());
+            self.put(self.offset, &name, ty, Some(self.unions))?;
+        }
+        match size.checked_next_multiple_of(align) {
+            Some(size) => self.advance(size),
+            None => Err(format!("union of {:#x} bytes is out of range", size).into()),
+        }
+    }
+
+    /// The laid out struct, erroring if the length of a `DynCollection` does not come from an integer field or an
+    /// inline one is followed by another field.
+    fn finish(self) -> Result<Struct, Box<EvalAltResult>> {
+        let native = Struct::with_align(self.fields, self.align);
+        for (offset, nf) in native.fields() {
+            // Its size is only known once read, a later field would alias its elements.
+            if is_unsized(&nf.ty)
+                && native
+                    .fields()
+                    .any(|(other, of)| other >= offset && !std::ptr::eq(of, nf))
+            {
+                return Err(format!(
+                    "`{}` has a dynamic length and must be the last field",
+                    nf.name
+                )
+                .into());
+            }
+        }
+        for (_, nf) in native.fields() {
+            let len_field = match &nf.ty {
+                Type::DynCollection(_, len_field) => len_field,
+                Type::Pointer32(pty) | Type::Pointer64(pty) => match &**pty {
+                    Type::DynCollection(_, len_field) => len_field,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            match native.get_field_from_name(len_field) {
+                Some(lf) if lf.ty.int_range().is_some() => {}
+                Some(lf) => {
+                    return Err(format!(
+                        "length of `{}` must be an integer field, not `{}`",
+                        nf.name,
+                        lf.ty.name()
+                    )
+                    .into())
                 }
                 None => {
-                    // We have yet to start a new field, we could expect a number of keywords (such as padding keyword `^`).
-                    match lh {
-                        "^" => Some("$symbol$"),
-                        _ => Some("$ident$"),
-                    }
+                    return Err(
+                        format!("no field `{}` for the length of `{}`", len_field, nf.name).into(),
+                    )
                 }
-            };
+            }
+        }
+        Ok(native)
+    }
+}
 
-            match res {
-                Some(expected) => Ok(Some(expected.into())),
-                // Expect field ending.
-                None => match lh {
-                    // TODO: We are letting the last field include the `}`, this might make us vulnerable to some nasty parsing issues.
-                    "}" => Ok(Some("}".into())),
-                    ";" => Ok(None),
-                    _ => Ok(Some(",".into())),
-                },
+/// Whether `ty` stores a `DynCollection` inline, not behind a pointer.
+fn is_unsized(ty: &Type) -> bool {
+    match ty {
+        Type::DynCollection(_, _) => true,
+        Type::Struct(native) => native.fields().any(|(_, nf)| is_unsized(&nf.ty)),
+        _ => false,
+    }
+}
+
+/// Attributes of `native(...)`.
+struct Attributes {
+    /// Allow fields to overlap outside of unions.
+    overlap: bool,
+    /// Largest alignment of any field.
+    pack: u32,
+}
+
+/// Parse the attributes of `native(...)` up to its closing `)`.
+///
+/// Structs are packed unless `align` or `pack = N` asks for C/MSVC layout, where `N` limits field alignment like
+/// `#pragma pack(N)`.
+fn parse_attributes<'a, 'e: 'a>(
+    exprs: &mut impl Iterator<Item = &'a Expression<'e>>,
+) -> Result<Attributes, Box<EvalAltResult>> {
+    let mut overlap = false;
+    let mut pack: Option<u32> = None;
+    loop {
+        match exprs.next().and_then(|expr| expr.get_string_value()) {
+            Some(")") => {
+                return Ok(Attributes {
+                    overlap,
+                    pack: pack.unwrap_or(1),
+                })
             }
+            Some("overlap") => overlap = true,
+            Some("align") => pack = Some(pack.unwrap_or(u32::MAX)),
+            Some("pack") => {
+                let value = exprs
+                    .nth(1)
+                    .and_then(|expr| expr.get_literal_value::<rhai::INT>())
+                    .and_then(|value| u32::try_from(value).ok())
+                    .filter(|value| value.is_power_of_two())
+                    .ok_or("`pack` must be a constant power of two")?;
+                pack = Some(pack.map_or(value, |pack| pack.min(value)));
+            }
+            Some(attr) => return Err(format!("unknown native attribute `{}`", attr).into()),
+            None => return Err("unterminated native attributes".into()),
         }
-        _ => unreachable!(),
     }
 }
 
+/// Evaluate the type of the field `name`, including an implicit `Struct(*)` and a trailing bit field group.
+fn field_type<'a, 'e: 'a>(
+    context: &mut EvalContext,
+    name: &str,
+    exprs: &mut std::iter::Peekable<impl Iterator<Item = &'a Expression<'e>>>,
+) -> Result<Type, Box<EvalAltResult>> {
+    let field_type = match exprs
+        .next()
+        .and_then(|expr| context.eval_expression_tree(expr).ok())
+    {
+        Some(field_type) => field_type,
+        None => return Err(format!("failed to retrieve type for `{}`", name).into()),
+    };
+    if !field_type.is_variant() {
+        return Err(format!(
+            "cannot cast field_type from the primitive `{}`",
+            field_type.type_name()
+        )
+        .into());
+    }
+
+    if field_type.type_id() == TypeId::of::<Struct>() {
+        // Implicit `Struct(*)`.
+        return Ok(Type::Struct(field_type.cast()));
+    }
+
+    // Explicit `Struct(*)` and other types.
+    let native_type: Type = field_type.cast();
+    if exprs.peek().and_then(|expr| expr.get_string_value()) == Some("{") {
+        exprs.next();
+        return Ok(Type::Bitfield(Bitfield::new(
+            native_type,
+            parse_bits(exprs)?,
+        )?));
+    }
+    Ok(native_type)
+}
+
 fn implement_native(
     context: &mut EvalContext,
     inputs: &[Expression],
 ) -> Result<Dynamic, Box<EvalAltResult>> {
-    let native_name = inputs[0].get_string_value().unwrap();
-    let mut native = Struct::new(BTreeMap::new());
+    let mut expr_iter = inputs.iter().peekable();
+    let attrs = match expr_iter.peek().and_then(|expr| expr.get_string_value()) {
+        Some("(") => {
+            expr_iter.next();
+            parse_attributes(&mut expr_iter)?
+        }
+        _ => Attributes {
+            overlap: false,
+            pack: 1,
+        },
+    };
+
+    let native_name = expr_iter.next().unwrap().get_string_value().unwrap();
+    let rest = &inputs[inputs.len() - expr_iter.len() - 1..];
+    match native_name {
+        "enum" => return implement_enum(context, rest, false),
+        "flags" => return implement_enum(context, rest, true),
+        _ => {}
+    }
+    let mut layout = Layout::new(attrs);
 
-    let mut offset = 0u32;
-    let mut expr_iter = inputs.iter().skip(1);
     while let Some(expr) = expr_iter.next() {
         match expr.get_string_value() {
             Some(keyword) => match keyword {
@@ -266,52 +1205,39 @@ fn implement_native(
                     // Get the pad size from next expression and add it to the current offset.
                     if let Some(padding_offset) = expr_iter.next().and_then(|expr| {
                         expr.get_literal_value::<rhai::INT>()
-                            .map(|i| i.abs() as u32)
+                            .and_then(|i| u32::try_from(i.unsigned_abs()).ok())
                     }) {
-                        offset += padding_offset;
+                        layout.advance(padding_offset)?;
                     } else {
                         return Err("padding must be a constant literal".into());
                     }
                 }
-                // Regular field.
-                _ => {
-                    if let Some(field_type) = expr_iter
+                // Explicit offset of the next field.
+                "@" => {
+                    match expr_iter
                         .next()
-                        .and_then(|expr| context.eval_expression_tree(expr).ok())
+                        .and_then(|expr| expr.get_literal_value::<rhai::INT>())
+                        .and_then(|offset| u32::try_from(offset).ok())
                     {
-                        if field_type.is_variant() {
-                            // TODO: Refactor this please.
-                            if field_type.type_id() == TypeId::of::<Struct>() {
-                                // Implicit `Struct(*)`.
-                                let native_struct: Struct = field_type.cast();
-                                native.0.insert(
-                                    offset,
-                                    Field::new(
-                                        keyword.to_string(),
-                                        Type::Struct(native_struct.clone()),
-                                    ),
-                                );
-                                offset += native_struct.size();
-                            } else {
-                                // Explicit `Struct(*)` and other types.
-                                let native_type: Type = field_type.cast();
-                                native.0.insert(
-                                    offset,
-                                    Field::new(keyword.to_string(), native_type.clone()),
-                                );
-                                offset += native_type.size();
-                            }
-                        } else {
-                            return Err(format!(
-                                "cannot cast field_type from the primitive `{}`",
-                                field_type.type_name()
+                        Some(offset) => layout.seek(offset),
+                        None => {
+                            return Err(
+                                "field offset must be a non-negative constant literal".into()
                             )
-                            .into());
                         }
-                    } else {
-                        return Err(format!("failed to retrieve type for `{}`", keyword).into());
                     }
                 }
+                "union" => {
+                    // Skip the opening `{`.
+                    expr_iter.next();
+                    layout.open_union();
+                }
+                "}" => layout.close_union()?,
+                // Regular field.
+                _ => {
+                    let native_type = field_type(context, keyword, &mut expr_iter)?;
+                    layout.place(keyword, native_type)?;
+                }
             },
             None => {
                 return Err(format!(
@@ -322,6 +1248,7 @@ fn implement_native(
             }
         }
     }
+    let native = layout.finish()?;
 
     // TODO: Maybe instead return the type?
     context
@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 
-use memflow::prelude::{OsInner, OsInstanceArcBox};
+use memflow::prelude::{Os, OsInstanceArcBox};
 
 use rhai::plugin::*;
 
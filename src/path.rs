@@ -0,0 +1,347 @@
+use std::{fmt, str::FromStr};
+
+use memflow::{prelude::Process, types::Address};
+
+use rhai::plugin::*;
+
+use crate::{
+    memory::offset_addr,
+    process::{process_functions, resolve_chain, SharedProcess},
+};
+
+/// Syntax error in a pointer path, `column` is 1-based and counts characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl From<PathError> for Box<EvalAltResult> {
+    fn from(err: PathError) -> Self {
+        err.to_string().into()
+    }
+}
+
+/// Term of the base expression of a pointer path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    /// Base address of the module with this name.
+    Module(String),
+    /// Signed constant offset.
+    Offset(rhai::INT),
+}
+
+/// Parsed pointer path such as `Game.dll+0x1234 -> 0x10 -> +0x8`.
+///
+/// The base is the sum of its terms, each `->` then dereferences the current address and adds the following offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerPath {
+    pub base: Vec<Term>,
+    pub offsets: Vec<rhai::INT>,
+}
+
+impl PointerPath {
+    /// Parse a pointer path.
+    ///
+    /// Numbers are hexadecimal with a `0x` prefix and decimal otherwise, any other word is a module name. Module
+    /// names which are not made of letters, digits, `_` and `.` can be quoted with `"` or backticks.
+    pub fn parse(input: &str) -> Result<Self, PathError> {
+        Parser::new(input)?.parse()
+    }
+
+    /// Resolve the path in `proc`, modules are looked up by name and pointers are read with the process pointer width.
+    pub fn resolve(&self, proc: &mut SharedProcess) -> Result<Address, Box<EvalAltResult>> {
+        let base = self
+            .base
+            .iter()
+            .try_fold(Address::NULL, |addr, term| match term {
+                Term::Module(name) => process_functions::get_module_from_name(proc, name)
+                    .map(|mi| addr + mi.base.to_umem())
+                    .map_err(|e| format!("module `{}`: {}", name, e).into()),
+                Term::Offset(offset) => offset_addr(addr, *offset),
+            })?;
+
+        let prc = proc.get_mut();
+        let arch = prc.info().proc_arch.into();
+        resolve_chain(prc, arch, base, &self.offsets)
+    }
+}
+
+impl FromStr for PointerPath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for PointerPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.base.iter().enumerate() {
+            match term {
+                Term::Module(name) if i == 0 => write!(f, "{}", Quoted(name))?,
+                Term::Module(name) => write!(f, "+{}", Quoted(name))?,
+                Term::Offset(offset) if i == 0 && *offset >= 0 => write!(f, "{:#x}", offset)?,
+                Term::Offset(offset) => write!(f, "{}", Signed(*offset))?,
+            }
+        }
+        for offset in &self.offsets {
+            match *offset >= 0 {
+                true => write!(f, " -> {:#x}", offset)?,
+                false => write!(f, " -> {}", Signed(*offset))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Quoted<'a>(&'a str);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.chars().all(is_word_char) && parse_number(self.0).is_none() {
+            true => write!(f, "{}", self.0),
+            false => write!(f, "`{}`", self.0),
+        }
+    }
+}
+
+struct Signed(rhai::INT);
+
+impl fmt::Display for Signed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 < 0 {
+            true => write!(f, "-{:#x}", self.0.unsigned_abs()),
+            false => write!(f, "+{:#x}", self.0),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// `Some` if `word` is a number, `Some(None)` if it is a number which does not fit.
+fn parse_number(word: &str) -> Option<Option<rhai::INT>> {
+    if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(rhai::INT::from_str_radix(hex, 16).ok());
+        }
+    } else if word.chars().all(|c| c.is_ascii_digit()) {
+        return Some(word.parse().ok());
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Arrow,
+    Plus,
+    Minus,
+    Word(String),
+    Quoted(String),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Arrow => write!(f, "`->`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Quoted(name) => write!(f, "module `{}`", name),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, PathError> {
+        let chars: Vec<char> = input.chars().collect();
+        let mut tokens = vec![];
+        let mut i = 0;
+
+        while i < chars.len() {
+            let column = i + 1;
+            match chars[i] {
+                c if c.is_whitespace() => i += 1,
+                '-' if chars.get(i + 1) == Some(&'>') => {
+                    tokens.push((column, Token::Arrow));
+                    i += 2;
+                }
+                '-' => {
+                    tokens.push((column, Token::Minus));
+                    i += 1;
+                }
+                '+' => {
+                    tokens.push((column, Token::Plus));
+                    i += 1;
+                }
+                quote @ ('"' | '`') => {
+                    let len = chars[i + 1..]
+                        .iter()
+                        .position(|c| *c == quote)
+                        .ok_or_else(|| PathError {
+                            column,
+                            message: "unterminated module name".into(),
+                        })?;
+                    let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                    if name.is_empty() {
+                        return Err(PathError {
+                            column,
+                            message: "empty module name".into(),
+                        });
+                    }
+                    tokens.push((column, Token::Quoted(name)));
+                    i += len + 2;
+                }
+                c if is_word_char(c) => {
+                    let len = chars[i..].iter().take_while(|c| is_word_char(**c)).count();
+                    tokens.push((column, Token::Word(chars[i..i + len].iter().collect())));
+                    i += len;
+                }
+                c => {
+                    return Err(PathError {
+                        column,
+                        message: format!("unexpected character `{}`", c),
+                    })
+                }
+            }
+        }
+        tokens.push((chars.len() + 1, Token::End));
+
+        Ok(Self { tokens, pos: 0 })
+    }
+
+    fn peek(&self) -> &(usize, Token) {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> (usize, Token) {
+        let token = self.tokens[self.pos].clone();
+        if token.1 != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn parse(mut self) -> Result<PointerPath, PathError> {
+        let base = self.parse_expr(true)?;
+        let mut offsets = vec![];
+        while self.peek().1 == Token::Arrow {
+            self.next();
+            let terms = self.parse_expr(false)?;
+            let column = self.peek().0;
+            let offset = terms
+                .iter()
+                .try_fold(0 as rhai::INT, |acc, term| match term {
+                    Term::Offset(offset) => acc.checked_add(*offset),
+                    Term::Module(_) => None,
+                })
+                .ok_or_else(|| PathError {
+                    column,
+                    message: "offset overflowed".into(),
+                })?;
+            offsets.push(offset);
+        }
+
+        match self.next() {
+            (_, Token::End) => Ok(PointerPath { base, offsets }),
+            (column, token) => Err(PathError {
+                column,
+                message: format!("expected `->`, `+` or `-` but found {}", token),
+            }),
+        }
+    }
+
+    /// `[sign] term (sign term)*`, modules are only allowed in the base expression.
+    fn parse_expr(&mut self, base: bool) -> Result<Vec<Term>, PathError> {
+        let mut terms = vec![];
+        let mut negative = match self.peek().1 {
+            Token::Plus => {
+                self.next();
+                false
+            }
+            Token::Minus => {
+                self.next();
+                true
+            }
+            _ => false,
+        };
+
+        loop {
+            let (column, token) = self.next();
+            let term = match token {
+                Token::Word(word) => match parse_number(&word) {
+                    Some(Some(num)) => Term::Offset(if negative { -num } else { num }),
+                    Some(None) => {
+                        return Err(PathError {
+                            column,
+                            message: format!("number `{}` is too large", word),
+                        })
+                    }
+                    None => Term::Module(word),
+                },
+                Token::Quoted(name) => Term::Module(name),
+                token => {
+                    return Err(PathError {
+                        column,
+                        message: match base && terms.is_empty() {
+                            true => format!("expected a module name or number but found {}", token),
+                            false => format!("expected a number but found {}", token),
+                        },
+                    })
+                }
+            };
+
+            if let Term::Module(name) = &term {
+                if !base {
+                    return Err(PathError {
+                        column,
+                        message: format!("module `{}` is only allowed before the first `->`", name),
+                    });
+                }
+                if negative {
+                    return Err(PathError {
+                        column,
+                        message: format!("cannot subtract module `{}`", name),
+                    });
+                }
+            }
+            terms.push(term);
+
+            negative = match self.peek().1 {
+                Token::Plus => false,
+                Token::Minus => true,
+                _ => break Ok(terms),
+            };
+            self.next();
+        }
+    }
+}
+
+/// Pointer path functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod path_functions {
+    /// Parse and resolve a pointer path such as `"Game.dll+0x1234 -> 0x10 -> +0x8"`.
+    #[rhai_fn(pure, global, return_raw, name = "eval_addr")]
+    pub fn eval_addr(proc: &mut SharedProcess, path: &str) -> Result<Address, Box<EvalAltResult>> {
+        PointerPath::parse(path)?.resolve(proc)
+    }
+}
@@ -0,0 +1,297 @@
+use std::{fmt, str::FromStr};
+
+use memflow::{
+    prelude::{MemoryView, ModuleInfo},
+    types::{umem, Address},
+};
+
+use rhai::plugin::*;
+
+use crate::{
+    memory::{offset_addr, read_pages, read_to_dyn, CHUNK_SIZE},
+    native::Type,
+    process::{process_functions::module_info_functions, SharedProcess},
+};
+
+/// IDA-style byte signature such as `48 8B 05 ?? ?? ?? ?? 48 85 C0`, `None` bytes are wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(pub Vec<Option<u8>>);
+
+impl Pattern {
+    /// Parse whitespace separated hex bytes, `?` and `??` are wildcards.
+    pub fn parse(input: &str) -> Result<Self, Box<EvalAltResult>> {
+        let bytes = input
+            .split_whitespace()
+            .enumerate()
+            .map(|(i, byte)| match byte {
+                "?" | "??" => Ok(None),
+                _ if byte.len() == 2 => u8::from_str_radix(byte, 16)
+                    .map(Some)
+                    .map_err(|_| format!("invalid pattern byte {} `{}`", i + 1, byte).into()),
+                _ => Err(format!("invalid pattern byte {} `{}`", i + 1, byte).into()),
+            })
+            .collect::<Result<Vec<_>, Box<EvalAltResult>>>()?;
+
+        if !bytes.iter().any(Option::is_some) {
+            return Err("pattern must contain at least one non-wildcard byte".into());
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Offsets of every match in `buf`.
+    pub fn find_in<'a>(&'a self, buf: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        // Matches are anchored on the first non-wildcard byte to skip most of `buf` quickly.
+        let (anchor, anchor_byte) = self
+            .0
+            .iter()
+            .enumerate()
+            .find_map(|(i, b)| b.map(|b| (i, b)))
+            .unwrap_or_default();
+        let last = buf.len().saturating_sub(self.0.len() - 1);
+
+        (0..last).filter(move |&i| {
+            buf[i + anchor] == anchor_byte
+                && self
+                    .0
+                    .iter()
+                    .zip(&buf[i..])
+                    .all(|(p, b)| p.is_none() || *p == Some(*b))
+        })
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Box<EvalAltResult>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match byte {
+                Some(byte) => write!(f, "{:02X}", byte)?,
+                None => write!(f, "??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scan `size` bytes at `start` for `pattern`, stopping at the first match if `first` is set.
+///
+/// Memory is read in large chunks, pages which cannot be read are skipped and no match spans them.
+pub fn find_pattern_in(
+    mem: &mut impl MemoryView,
+    pattern: &Pattern,
+    start: Address,
+    size: umem,
+    first: bool,
+) -> Result<Vec<Address>, Box<EvalAltResult>> {
+    let overlap = pattern.0.len() - 1;
+
+    let mut matches = vec![];
+    let mut done = false;
+    // Contiguous readable bytes which have not been searched yet, starting at `run_addr`.
+    let mut run_addr = start.to_umem();
+    let mut run: Vec<u8> = vec![];
+
+    let search = |run_addr: umem, run: &[u8], matches: &mut Vec<Address>| {
+        matches.extend(
+            pattern
+                .find_in(run)
+                .take(if first { 1 } else { usize::MAX })
+                .map(|i| Address::from(run_addr + i as umem)),
+        );
+        first && !matches.is_empty()
+    };
+
+    read_pages(mem, start, size, |addr, _, page| {
+        match page {
+            Some(page) => {
+                if run.is_empty() {
+                    run_addr = addr.to_umem();
+                }
+                run.extend_from_slice(&page);
+                if (run.len() as umem) < CHUNK_SIZE {
+                    return Ok(true);
+                }
+                // Keep the tail of the run so matches crossing into the next page are still found.
+                done = search(run_addr, &run, &mut matches);
+                let keep = run.len().min(overlap);
+                run_addr += (run.len() - keep) as umem;
+                run.drain(..run.len() - keep);
+            }
+            None => {
+                done = search(run_addr, &run, &mut matches);
+                run.clear();
+            }
+        }
+        Ok(!done)
+    })?;
+
+    if !done {
+        search(run_addr, &run, &mut matches);
+    }
+    Ok(matches)
+}
+
+/// Target of the instruction at `addr` with a signed 32-bit displacement `disp_offset` bytes into it, relative
+/// displacements are taken from the end of the instruction i.e. `addr + insn_len + disp32`.
+pub fn resolve_rel32(
+    mem: &mut impl MemoryView,
+    addr: Address,
+    disp_offset: rhai::INT,
+    insn_len: rhai::INT,
+) -> Result<Address, Box<EvalAltResult>> {
+    let disp = read_to_dyn(mem, &Type::Int32, offset_addr(addr, disp_offset)?)?.as_int()?;
+    offset_addr(offset_addr(addr, insn_len)?, disp)
+}
+
+/// Target of the `call rel32` (`E8`) or `jmp rel32` (`E9`) at `addr`, `opcode` is checked before resolving.
+fn resolve_branch(
+    mem: &mut impl MemoryView,
+    addr: Address,
+    opcode: u8,
+    insn: &str,
+) -> Result<Address, Box<EvalAltResult>> {
+    match read_to_dyn(mem, &Type::UInt8, addr)?.as_int()? {
+        op if op == opcode as rhai::INT => resolve_rel32(mem, addr, 1, 5),
+        op => Err(format!(
+            "expected `{}` ({:02X}) at {:#x} but found {:02X}",
+            insn,
+            opcode,
+            addr.to_umem(),
+            op
+        )
+        .into()),
+    }
+}
+
+/// Pattern scanning functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod pattern_functions {
+    fn find(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        start: Address,
+        size: rhai::INT,
+        first: bool,
+    ) -> Result<Vec<Address>, Box<EvalAltResult>> {
+        let size =
+            umem::try_from(size).map_err(|_| format!("cannot scan negative size {}", size))?;
+        find_pattern_in(
+            proc.get_mut(),
+            &Pattern::parse(pattern)?,
+            start,
+            size,
+            first,
+        )
+    }
+
+    fn find_first(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        start: Address,
+        size: rhai::INT,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        Ok(find(proc, pattern, start, size, true)?
+            .pop()
+            .map_or(Dynamic::UNIT, Dynamic::from))
+    }
+
+    fn find_all(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        start: Address,
+        size: rhai::INT,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        Ok(find(proc, pattern, start, size, false)?
+            .into_iter()
+            .map(Dynamic::from)
+            .collect())
+    }
+
+    /// Address of the first match of `pattern` in `module`, or `()` if there is none.
+    #[rhai_fn(pure, global, return_raw, name = "find_pattern")]
+    pub fn find_pattern(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        mut module: ModuleInfo,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        let start = module_info_functions::get_base_addr(&mut module);
+        let size = module_info_functions::get_size(&mut module);
+        find_first(proc, pattern, start, size)
+    }
+
+    /// Address of the first match of `pattern` in `size` bytes at `start`, or `()` if there is none.
+    #[rhai_fn(pure, global, return_raw, name = "find_pattern")]
+    pub fn find_pattern_range(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        start: Address,
+        size: rhai::INT,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        find_first(proc, pattern, start, size)
+    }
+
+    /// Addresses of every match of `pattern` in `module`.
+    #[rhai_fn(pure, global, return_raw, name = "find_pattern_all")]
+    pub fn find_pattern_all(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        mut module: ModuleInfo,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let start = module_info_functions::get_base_addr(&mut module);
+        let size = module_info_functions::get_size(&mut module);
+        find_all(proc, pattern, start, size)
+    }
+
+    /// Addresses of every match of `pattern` in `size` bytes at `start`.
+    #[rhai_fn(pure, global, return_raw, name = "find_pattern_all")]
+    pub fn find_pattern_all_range(
+        proc: &mut SharedProcess,
+        pattern: &str,
+        start: Address,
+        size: rhai::INT,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        find_all(proc, pattern, start, size)
+    }
+
+    /// Resolve a relative 32-bit displacement, `proc.resolve_rel32(addr, 3, 7)` turns the `mov rax, [rip+disp32]` at
+    /// `addr` into the address it loads from.
+    #[rhai_fn(pure, global, return_raw, name = "resolve_rel32")]
+    pub fn resolve_rel32_fn(
+        proc: &mut SharedProcess,
+        addr: Address,
+        disp_offset: rhai::INT,
+        insn_len: rhai::INT,
+    ) -> Result<Address, Box<EvalAltResult>> {
+        resolve_rel32(proc.get_mut(), addr, disp_offset, insn_len)
+    }
+
+    /// Target of the `call rel32` at `addr`.
+    #[rhai_fn(pure, global, return_raw, name = "resolve_call")]
+    pub fn resolve_call(
+        proc: &mut SharedProcess,
+        addr: Address,
+    ) -> Result<Address, Box<EvalAltResult>> {
+        resolve_branch(proc.get_mut(), addr, 0xE8, "call rel32")
+    }
+
+    /// Target of the `jmp rel32` at `addr`.
+    #[rhai_fn(pure, global, return_raw, name = "resolve_jmp")]
+    pub fn resolve_jmp(
+        proc: &mut SharedProcess,
+        addr: Address,
+    ) -> Result<Address, Box<EvalAltResult>> {
+        resolve_branch(proc.get_mut(), addr, 0xE9, "jmp rel32")
+    }
+}
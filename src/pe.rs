@@ -0,0 +1,542 @@
+use memflow::{
+    prelude::{MemoryView, ModuleInfo},
+    types::{umem, Address},
+};
+
+use rhai::plugin::*;
+
+use crate::{
+    memory::{map_of, offset_addr, read_cstr, read_raw_checked, MAX_READ_LEN},
+    process::SharedProcess,
+};
+
+const DIRECTORY_NAMES: [&str; 16] = [
+    "export",
+    "import",
+    "resource",
+    "exception",
+    "security",
+    "basereloc",
+    "debug",
+    "architecture",
+    "globalptr",
+    "tls",
+    "load_config",
+    "bound_import",
+    "iat",
+    "delay_import",
+    "clr",
+    "reserved",
+];
+
+const DIR_EXPORT: usize = 0;
+const DIR_IMPORT: usize = 1;
+const DIR_BASERELOC: usize = 5;
+const DIR_DEBUG: usize = 6;
+const DIR_TLS: usize = 9;
+
+const DEBUG_TYPE_CODEVIEW: u32 = 2;
+
+/// Upper bound on the entries of any single table, guarding against garbage headers.
+const MAX_ENTRIES: usize = 0x10000;
+
+const MAX_NAME_LEN: usize = 0x400;
+
+/// Extent of the NT signature, COFF header and the largest (PE32+) optional header with all directories.
+const NT_HEADERS_SIZE: u32 = 24 + 112 + 16 * 8;
+
+/// Mapped PE image in process memory, every location is relative to `base`.
+pub struct PeImage<'a, M: MemoryView> {
+    mem: &'a mut M,
+    pub base: Address,
+    pub is_64: bool,
+    // Offset of the NT headers.
+    nt: u32,
+    pub directories: Vec<(u32, u32)>,
+}
+
+impl<'a, M: MemoryView> PeImage<'a, M> {
+    /// Validate the DOS and NT headers of the image at `base`.
+    pub fn new(mem: &'a mut M, base: Address) -> Result<Self, Box<EvalAltResult>> {
+        let mut image = Self {
+            mem,
+            base,
+            is_64: false,
+            nt: 0,
+            directories: vec![],
+        };
+
+        if image.bytes(0, 2)? != b"MZ" {
+            return Err(format!("no DOS signature at {:#x}", base.to_umem()).into());
+        }
+        image.nt = image.u32(0x3c)?;
+        // Fixed offsets into the NT headers cannot overflow once their whole extent fits.
+        rva_add(image.nt, NT_HEADERS_SIZE)?;
+        if image.bytes(image.nt, 4)? != b"PE\0\0" {
+            return Err(format!("no NT signature at {:#x}", (base + image.nt).to_umem()).into());
+        }
+        image.is_64 = match image.u16(image.nt + 24)? {
+            0x10b => false,
+            0x20b => true,
+            magic => return Err(format!("unknown optional header magic {:#x}", magic).into()),
+        };
+
+        let (count, dirs) = match image.is_64 {
+            true => (image.u32(image.opt() + 108)?, image.opt() + 112),
+            false => (image.u32(image.opt() + 92)?, image.opt() + 96),
+        };
+        let count = count.min(DIRECTORY_NAMES.len() as u32);
+        let raw = image.bytes(dirs, count as usize * 8)?;
+        image.directories = raw
+            .chunks_exact(8)
+            .map(|d| (le32(&d[0..]), le32(&d[4..])))
+            .collect();
+
+        Ok(image)
+    }
+
+    fn opt(&self) -> u32 {
+        self.nt + 24
+    }
+
+    fn addr(&self, rva: u32) -> Address {
+        self.base + rva as umem
+    }
+
+    fn bytes(&mut self, rva: u32, len: usize) -> Result<Vec<u8>, Box<EvalAltResult>> {
+        let addr = self.addr(rva);
+        read_raw_checked(self.mem, addr, len)
+    }
+
+    fn u16(&mut self, rva: u32) -> Result<u16, Box<EvalAltResult>> {
+        Ok(le16(&self.bytes(rva, 2)?))
+    }
+
+    fn u32(&mut self, rva: u32) -> Result<u32, Box<EvalAltResult>> {
+        Ok(le32(&self.bytes(rva, 4)?))
+    }
+
+    /// Pointer sized value at `rva`.
+    fn ptr(&mut self, rva: u32) -> Result<u64, Box<EvalAltResult>> {
+        let raw = self.bytes(rva, self.ptr_size())?;
+        Ok(match self.is_64 {
+            true => le64(&raw),
+            false => le32(&raw).into(),
+        })
+    }
+
+    fn ptr_size(&self) -> usize {
+        if self.is_64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn cstr(&mut self, rva: u32) -> Result<String, Box<EvalAltResult>> {
+        let addr = self.addr(rva);
+        read_cstr(self.mem, addr, MAX_NAME_LEN)
+    }
+
+    /// Directory `idx` if present.
+    fn directory(&self, idx: usize) -> Option<(u32, u32)> {
+        self.directories
+            .get(idx)
+            .copied()
+            .filter(|(rva, size)| *rva != 0 && *size != 0)
+    }
+
+    /// COFF and optional header fields.
+    pub fn headers(&mut self) -> Result<rhai::Map, Box<EvalAltResult>> {
+        let coff = self.bytes(self.nt + 4, 20)?;
+        let opt = self.opt();
+        let (image_base, tail) = match self.is_64 {
+            true => (self.ptr(opt + 24)?, opt + 72),
+            false => (self.u32(opt + 28)?.into(), opt + 72),
+        };
+        let entry_point = self.u32(opt + 16)?;
+        let stack_heap = self.bytes(tail, self.ptr_size() * 4)?;
+        let stack_heap: Vec<Dynamic> = stack_heap
+            .chunks_exact(self.ptr_size())
+            .map(|raw| match self.is_64 {
+                true => Dynamic::from_int(le64(raw) as rhai::INT),
+                false => Dynamic::from_int(le32(raw) as rhai::INT),
+            })
+            .collect();
+
+        Ok(map_of([
+            ("e_lfanew", Dynamic::from_int(self.nt as rhai::INT)),
+            ("machine", int(le16(&coff[0..]))),
+            ("number_of_sections", int(le16(&coff[2..]))),
+            ("timestamp", int(le32(&coff[4..]))),
+            ("characteristics", int(le16(&coff[18..]))),
+            ("is_64", Dynamic::from_bool(self.is_64)),
+            ("magic", int(self.u16(opt)?)),
+            ("size_of_code", int(self.u32(opt + 4)?)),
+            ("entry_point", self.rva_addr(entry_point)),
+            ("base_of_code", int(self.u32(opt + 20)?)),
+            ("image_base", Dynamic::from(Address::from(image_base))),
+            ("section_alignment", int(self.u32(opt + 32)?)),
+            ("file_alignment", int(self.u32(opt + 36)?)),
+            ("os_version", int(self.u16(opt + 40)?)),
+            ("subsystem_version", int(self.u16(opt + 48)?)),
+            ("size_of_image", int(self.u32(opt + 56)?)),
+            ("size_of_headers", int(self.u32(opt + 60)?)),
+            ("checksum", int(self.u32(opt + 64)?)),
+            ("subsystem", int(self.u16(opt + 68)?)),
+            ("dll_characteristics", int(self.u16(opt + 70)?)),
+            ("stack_reserve", stack_heap[0].clone()),
+            ("stack_commit", stack_heap[1].clone()),
+            ("heap_reserve", stack_heap[2].clone()),
+            ("heap_commit", stack_heap[3].clone()),
+        ]))
+    }
+
+    /// `()` for a zero RVA, the absolute address otherwise.
+    fn rva_addr(&self, rva: u32) -> Dynamic {
+        match rva {
+            0 => Dynamic::UNIT,
+            rva => Dynamic::from(self.addr(rva)),
+        }
+    }
+
+    /// Every data directory, including empty ones.
+    pub fn data_directories(&self) -> rhai::Array {
+        self.directories
+            .iter()
+            .zip(DIRECTORY_NAMES)
+            .map(|((rva, size), name)| {
+                Dynamic::from_map(map_of([
+                    ("name", name.into()),
+                    ("addr", self.rva_addr(*rva)),
+                    ("rva", int(*rva)),
+                    ("size", int(*size)),
+                ]))
+            })
+            .collect()
+    }
+
+    /// Section headers.
+    pub fn sections(&mut self) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let count = le16(&self.bytes(self.nt + 6, 2)?) as usize;
+        let opt_size = self.u16(self.nt + 20)? as u32;
+        let raw = self.bytes(rva_add(self.opt(), opt_size)?, count * 40)?;
+
+        Ok(raw
+            .chunks_exact(40)
+            .map(|sh| {
+                let name = &sh[..8];
+                let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(8)];
+                Dynamic::from_map(map_of([
+                    ("name", String::from_utf8_lossy(name).into_owned().into()),
+                    ("addr", Dynamic::from(self.addr(le32(&sh[12..])))),
+                    ("rva", int(le32(&sh[12..]))),
+                    ("virtual_size", int(le32(&sh[8..]))),
+                    ("raw_size", int(le32(&sh[16..]))),
+                    ("raw_offset", int(le32(&sh[20..]))),
+                    ("characteristics", int(le32(&sh[36..]))),
+                ]))
+            })
+            .collect())
+    }
+
+    /// Exports by ordinal, unnamed exports have a `()` name and forwarded exports a `forward` string instead of an
+    /// address.
+    pub fn exports(&mut self) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let (dir, dir_size) = match self.directory(DIR_EXPORT) {
+            Some(dir) => dir,
+            None => return Ok(vec![]),
+        };
+        let dir_end = rva_add(dir, dir_size)?;
+        let raw = self.bytes(dir, 40)?;
+        let ordinal_base = le32(&raw[16..]);
+        let num_funcs = checked_count(le32(&raw[20..]), "exports")?;
+        let num_names = checked_count(le32(&raw[24..]), "export names")?;
+
+        let funcs = self.bytes(le32(&raw[28..]), num_funcs * 4)?;
+        let names = self.bytes(le32(&raw[32..]), num_names * 4)?;
+        let ordinals = self.bytes(le32(&raw[36..]), num_names * 2)?;
+
+        let mut func_names = vec![None; num_funcs];
+        for (name, idx) in names.chunks_exact(4).zip(ordinals.chunks_exact(2)) {
+            if let Some(slot) = func_names.get_mut(le16(idx) as usize) {
+                *slot = Some(self.cstr(le32(name))?);
+            }
+        }
+
+        let mut exports = vec![];
+        for (idx, (func, name)) in funcs.chunks_exact(4).zip(func_names).enumerate() {
+            let rva = le32(func);
+            if rva == 0 {
+                continue;
+            }
+            // Exports pointing back into the export directory are forwarded to another module.
+            let (addr, forward) = match (dir..dir_end).contains(&rva) {
+                true => (Dynamic::UNIT, self.cstr(rva)?.into()),
+                false => (Dynamic::from(self.addr(rva)), Dynamic::UNIT),
+            };
+            exports.push(Dynamic::from_map(map_of([
+                ("name", name.map_or(Dynamic::UNIT, Dynamic::from)),
+                ("ordinal", int(ordinal_base + idx as u32)),
+                ("addr", addr),
+                ("forward", forward),
+            ])));
+        }
+        Ok(exports)
+    }
+
+    /// Imports of every module, imports by ordinal have a `()` name and hint.
+    pub fn imports(&mut self) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let (dir, _) = match self.directory(DIR_IMPORT) {
+            Some(dir) => dir,
+            None => return Ok(vec![]),
+        };
+        let ptr_size = self.ptr_size();
+        let ordinal_flag = 1u64 << (ptr_size * 8 - 1);
+
+        let mut imports = vec![];
+        for idx in 0..MAX_ENTRIES as u32 {
+            let raw = self.bytes(rva_add(dir, idx * 20)?, 20)?;
+            if raw.iter().all(|b| *b == 0) {
+                return Ok(imports);
+            }
+            let module = self.cstr(le32(&raw[12..]))?;
+            let iat = le32(&raw[16..]);
+            // Bound images overwrite the IAT, the lookup table still holds the names.
+            let lookup = match le32(&raw[0..]) {
+                0 => iat,
+                ilt => ilt,
+            };
+
+            for idx in 0..MAX_ENTRIES as u32 {
+                let slot = idx * ptr_size as u32;
+                let thunk = self.ptr(rva_add(lookup, slot)?)?;
+                if thunk == 0 {
+                    break;
+                }
+                let (name, hint, ordinal) = match thunk & ordinal_flag {
+                    0 => {
+                        let rva = thunk as u32;
+                        (
+                            self.cstr(rva_add(rva, 2)?)?.into(),
+                            int(self.u16(rva)?),
+                            Dynamic::UNIT,
+                        )
+                    }
+                    _ => (Dynamic::UNIT, Dynamic::UNIT, int(thunk as u16)),
+                };
+                imports.push(Dynamic::from_map(map_of([
+                    ("dll", module.as_str().into()),
+                    ("name", name),
+                    ("hint", hint),
+                    ("ordinal", ordinal),
+                    ("slot", Dynamic::from(self.addr(rva_add(iat, slot)?))),
+                ])));
+            }
+        }
+        Err("too many import descriptors".into())
+    }
+
+    /// Base relocations, padding entries are skipped.
+    pub fn relocations(&mut self) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let (dir, dir_size) = match self.directory(DIR_BASERELOC) {
+            Some(dir) => dir,
+            None => return Ok(vec![]),
+        };
+        let raw = self.bytes(dir, dir_len(dir_size)?)?;
+
+        let mut relocs = vec![];
+        let mut block = raw.as_slice();
+        while block.len() >= 8 {
+            let page = le32(block);
+            let size = le32(&block[4..]) as usize;
+            if size < 8 || size > block.len() {
+                return Err(format!("invalid relocation block size {:#x}", size).into());
+            }
+            for entry in block[8..size].chunks_exact(2) {
+                let entry = le16(entry);
+                if entry >> 12 != 0 {
+                    relocs.push(Dynamic::from_map(map_of([
+                        (
+                            "addr",
+                            Dynamic::from(self.addr(rva_add(page, (entry & 0xfff) as u32)?)),
+                        ),
+                        ("kind", int(entry >> 12)),
+                    ])));
+                }
+            }
+            block = &block[size..];
+        }
+        Ok(relocs)
+    }
+
+    /// TLS directory, its addresses are virtual addresses as relocated by the loader.
+    pub fn tls(&mut self) -> Result<Dynamic, Box<EvalAltResult>> {
+        let (dir, _) = match self.directory(DIR_TLS) {
+            Some(dir) => dir,
+            None => return Ok(Dynamic::UNIT),
+        };
+        let ptr_size = self.ptr_size() as u32;
+        let [start, end, index, callbacks] =
+            [0, 1, 2, 3].map(|i| self.ptr(rva_add(dir, i * ptr_size)?).map(Address::from));
+        let callbacks = callbacks?;
+
+        // The callback array is a NUL terminated list of virtual addresses, not RVAs.
+        let mut callback_addrs = vec![];
+        if !callbacks.is_null() {
+            for idx in 0..MAX_ENTRIES as u32 {
+                let slot = offset_addr(callbacks, (idx * ptr_size) as rhai::INT)?;
+                let raw = read_raw_checked(self.mem, slot, ptr_size as usize)?;
+                let callback = match self.is_64 {
+                    true => le64(&raw),
+                    false => le32(&raw).into(),
+                };
+                if callback == 0 {
+                    break;
+                }
+                callback_addrs.push(Dynamic::from(Address::from(callback)));
+            }
+        }
+
+        Ok(Dynamic::from_map(map_of([
+            ("start", Dynamic::from(start?)),
+            ("end", Dynamic::from(end?)),
+            ("index", Dynamic::from(index?)),
+            ("callbacks", callback_addrs.into()),
+        ])))
+    }
+
+    /// Debug directory entries, CodeView (`RSDS`) entries include the PDB GUID, age and path.
+    pub fn debug(&mut self) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let (dir, dir_size) = match self.directory(DIR_DEBUG) {
+            Some(dir) => dir,
+            None => return Ok(vec![]),
+        };
+        let raw = self.bytes(dir, dir_len(dir_size)? / 28 * 28)?;
+
+        let mut entries = vec![];
+        for entry in raw.chunks_exact(28) {
+            let kind = le32(&entry[12..]);
+            let size = le32(&entry[16..]);
+            let rva = le32(&entry[20..]);
+
+            let codeview = match kind == DEBUG_TYPE_CODEVIEW && rva != 0 && size >= 24 {
+                true if self.bytes(rva, 4)? == b"RSDS" => {
+                    let cv = self.bytes(rva_add(rva, 4)?, 20)?;
+                    Dynamic::from_map(map_of([
+                        ("guid", format_guid(&cv[..16]).into()),
+                        ("age", int(le32(&cv[16..]))),
+                        ("path", self.cstr(rva_add(rva, 24)?)?.into()),
+                    ]))
+                }
+                _ => Dynamic::UNIT,
+            };
+            entries.push(Dynamic::from_map(map_of([
+                ("kind", int(kind)),
+                ("timestamp", int(le32(&entry[4..]))),
+                ("size", int(size)),
+                ("addr", self.rva_addr(rva)),
+                ("codeview", codeview),
+            ])));
+        }
+        Ok(entries)
+    }
+
+    /// Every part of the image as a single map.
+    pub fn parse(&mut self) -> Result<rhai::Map, Box<EvalAltResult>> {
+        Ok(map_of([
+            ("base", Dynamic::from(self.base)),
+            ("headers", self.headers()?.into()),
+            ("data_directories", self.data_directories().into()),
+            ("sections", self.sections()?.into()),
+            ("exports", self.exports()?.into()),
+            ("imports", self.imports()?.into()),
+            ("relocations", self.relocations()?.into()),
+            ("tls", self.tls()?),
+            ("debug_entries", self.debug()?.into()),
+        ]))
+    }
+}
+
+fn int(val: impl Into<rhai::INT>) -> Dynamic {
+    Dynamic::from_int(val.into())
+}
+
+/// `rva + offset`, erroring on overflow instead of wrapping around the image.
+fn rva_add(rva: u32, offset: u32) -> Result<u32, Box<EvalAltResult>> {
+    rva.checked_add(offset)
+        .ok_or_else(|| format!("malformed PE: RVA {:#x} + {:#x} overflowed", rva, offset).into())
+}
+
+/// Size of a directory to read at once, erroring if it exceeds `MAX_READ_LEN`.
+fn dir_len(size: u32) -> Result<usize, Box<EvalAltResult>> {
+    match size as usize {
+        len if len <= MAX_READ_LEN => Ok(len),
+        len => Err(format!(
+            "malformed PE: directory size {:#x} exceeds the limit of {:#x} bytes",
+            len, MAX_READ_LEN
+        )
+        .into()),
+    }
+}
+
+fn checked_count(count: u32, what: &str) -> Result<usize, Box<EvalAltResult>> {
+    match count as usize {
+        count if count <= MAX_ENTRIES => Ok(count),
+        count => Err(format!("too many {} ({})", what, count).into()),
+    }
+}
+
+fn le16(raw: &[u8]) -> u16 {
+    u16::from_le_bytes([raw[0], raw[1]])
+}
+
+fn le32(raw: &[u8]) -> u32 {
+    u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]])
+}
+
+fn le64(raw: &[u8]) -> u64 {
+    u64::from_le_bytes([
+        raw[0], raw[1], raw[2], raw[3], raw[4], raw[5], raw[6], raw[7],
+    ])
+}
+
+/// Registry format GUID, e.g. `00112233-4455-6677-8899-AABBCCDDEEFF`.
+fn format_guid(raw: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{}",
+        le32(raw),
+        le16(&raw[4..]),
+        le16(&raw[6..]),
+        raw[8],
+        raw[9],
+        raw[10..16]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<String>()
+    )
+}
+
+/// PE image functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod pe_functions {
+    /// Parse the PE image mapped at `base`.
+    #[rhai_fn(pure, global, return_raw, name = "pe")]
+    pub fn parse_pe(
+        proc: &mut SharedProcess,
+        base: Address,
+    ) -> Result<rhai::Map, Box<EvalAltResult>> {
+        PeImage::new(proc.get_mut(), base)?.parse()
+    }
+
+    /// Parse the PE image of `module`.
+    #[rhai_fn(pure, global, return_raw, name = "pe")]
+    pub fn parse_pe_module(
+        proc: &mut SharedProcess,
+        module: ModuleInfo,
+    ) -> Result<rhai::Map, Box<EvalAltResult>> {
+        parse_pe(proc, module.base)
+    }
+}
@@ -1,19 +1,104 @@
 use std::cell::RefCell;
 
 use memflow::{
-    prelude::{IntoProcessInstanceArcBox, ModuleInfo, Process, ProcessInfo},
+    prelude::{
+        ArchitectureObj, IntoProcessInstanceArcBox, MemoryView, ModuleInfo, Process, ProcessInfo,
+        SectionInfo,
+    },
     types::Address,
 };
 
 use rhai::plugin::*;
 
 use crate::{
-    memory::{read_to_dyn, NativePointer},
+    memory::{
+        memory_functions::pointer_functions, offset_addr, read_many_to_dyn, read_raw_checked,
+        read_to_dyn, write_from_dyn, NativePointer, MAX_READ_LEN,
+    },
     native::Type,
+    view::View,
 };
 
 pub type SharedProcess<'a> = RefCell<IntoProcessInstanceArcBox<'a>>;
 
+/// Symbol exported by a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleExport {
+    pub name: String,
+    pub addr: Address,
+}
+
+/// Symbol imported by a module, `addr` is the module base plus the import offset reported by memflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleImport {
+    pub name: String,
+    pub addr: Address,
+}
+
+/// Follow a multi-level pointer, at each level the current address is dereferenced and then offset.
+///
+/// `resolve_chain(mem, arch, base, &[0x18, 0x40])` is the same as `[[base] + 0x18] + 0x40`.
+pub fn resolve_chain(
+    mem: &mut impl MemoryView,
+    arch: ArchitectureObj,
+    base: Address,
+    offsets: &[rhai::INT],
+) -> Result<Address, Box<EvalAltResult>> {
+    offsets
+        .iter()
+        .enumerate()
+        .try_fold(base, |addr, (level, offset)| {
+            let raw = read_raw_checked(mem, addr, arch.size_addr()).map_err(|_| {
+                format!(
+                    "level {}: failed to read pointer at {:#x}",
+                    level + 1,
+                    addr.to_umem()
+                )
+            })?;
+            let ptr = match *raw {
+                [a, b, c, d] => Address::from(u32::from_ne_bytes([a, b, c, d])),
+                _ => Address::from(u64::from_ne_bytes(raw.try_into().unwrap_or_default())),
+            };
+            if ptr.is_null() {
+                return Err(
+                    format!("level {}: null pointer at {:#x}", level + 1, addr.to_umem()).into(),
+                );
+            }
+            offset_addr(ptr, *offset).map_err(|e| format!("level {}: {}", level + 1, e).into())
+        })
+}
+
+/// Case-insensitive glob match of `text` against `pattern`, `*` matches any number of characters and `?` a single one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // Backtrack to the last `*` on a mismatch, letting it absorb one more character.
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    star = Some((sp, st + 1));
+                    p = sp + 1;
+                    t = st + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 #[export_module]
 #[allow(dead_code)]
 #[warn(missing_docs)]
@@ -40,6 +125,48 @@ pub mod process_functions {
             .map_err(|e| e.as_str().into())
     }
 
+    /// Every module of the process.
+    #[rhai_fn(pure, return_raw, name = "modules")]
+    pub fn get_modules(proc: &mut SharedProcess) -> Result<rhai::Array, Box<EvalAltResult>> {
+        proc.borrow_mut()
+            .module_list()
+            .map(|modules| modules.into_iter().map(Dynamic::from).collect())
+            .map_err(|e| e.as_str().into())
+    }
+
+    /// Every module whose name matches the case-insensitive glob `pattern`, see `mod_like`.
+    #[rhai_fn(pure, return_raw, name = "modules")]
+    pub fn get_modules_like(
+        proc: &mut SharedProcess,
+        pattern: &str,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        proc.borrow_mut()
+            .module_list()
+            .map(|modules| {
+                modules
+                    .into_iter()
+                    .filter(|mi| glob_match(pattern, &mi.name))
+                    .map(Dynamic::from)
+                    .collect()
+            })
+            .map_err(|e| e.as_str().into())
+    }
+
+    /// First module whose name matches the case-insensitive glob `pattern`, `*` matches any number of characters and
+    /// `?` a single one.
+    #[rhai_fn(pure, return_raw, name = "mod_like")]
+    pub fn get_module_like(
+        proc: &mut SharedProcess,
+        pattern: &str,
+    ) -> Result<ModuleInfo, Box<EvalAltResult>> {
+        proc.borrow_mut()
+            .module_list()
+            .map_err(|e| e.as_str())?
+            .into_iter()
+            .find(|mi| glob_match(pattern, &mi.name))
+            .ok_or_else(|| format!("no module matching `{}`", pattern).into())
+    }
+
     #[rhai_fn(pure, return_raw, get = "addr")]
     pub fn get_addr(proc: &mut SharedProcess) -> Result<Address, Box<EvalAltResult>> {
         proc.borrow_mut()
@@ -64,6 +191,158 @@ pub mod process_functions {
         read_to_dyn(proc.get_mut(), &ptr.0, ptr.1)
     }
 
+    /// Read the element at `index` of the array `ptr` points to.
+    #[rhai_fn(pure, return_raw, name = "read")]
+    pub fn read_ptr_index(
+        proc: &mut SharedProcess,
+        ptr: NativePointer,
+        index: rhai::INT,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        let (ty, addr) = pointer_functions::add_num(ptr, index)?;
+        read_to_dyn(proc.get_mut(), &ty, addr)
+    }
+
+    /// Follow a multi-level pointer using the process pointer width, `proc.resolve(base, [0x18, 0x40])` is the same as
+    /// `[[base] + 0x18] + 0x40`.
+    #[rhai_fn(pure, return_raw, name = "resolve")]
+    pub fn resolve(
+        proc: &mut SharedProcess,
+        base: Address,
+        offsets: rhai::Array,
+    ) -> Result<Address, Box<EvalAltResult>> {
+        let offsets = offsets
+            .into_iter()
+            .map(|offset| {
+                offset
+                    .as_int()
+                    .map_err(|_| "offsets must be numbers".into())
+            })
+            .collect::<Result<Vec<_>, Box<EvalAltResult>>>()?;
+
+        let prc = proc.get_mut();
+        let arch = prc.info().proc_arch.into();
+        resolve_chain(prc, arch, base, &offsets)
+    }
+
+    /// Read `ty` at the end of a multi-level pointer, see `resolve`.
+    #[rhai_fn(pure, return_raw, name = "read_chain")]
+    pub fn read_chain(
+        proc: &mut SharedProcess,
+        ty: Type,
+        base: Address,
+        offsets: rhai::Array,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        let addr = resolve(proc, base, offsets)?;
+        read_to_dyn(proc.get_mut(), &ty, addr)
+    }
+
+    /// Read `len` raw bytes at `addr`.
+    #[rhai_fn(pure, return_raw, name = "read_bytes")]
+    pub fn read_bytes(
+        proc: &mut SharedProcess,
+        addr: Address,
+        len: rhai::INT,
+    ) -> Result<rhai::Blob, Box<EvalAltResult>> {
+        let len = match usize::try_from(len) {
+            Ok(len) if len <= MAX_READ_LEN => len,
+            Ok(_) => {
+                return Err(format!(
+                    "length {} exceeds the limit of {:#x} bytes",
+                    len, MAX_READ_LEN
+                )
+                .into())
+            }
+            Err(_) => return Err(format!("cannot read negative length {}", len).into()),
+        };
+        read_raw_checked(proc.get_mut(), addr, len)
+    }
+
+    /// Write the raw bytes of `blob` at `addr`.
+    #[rhai_fn(pure, return_raw, name = "write_bytes")]
+    pub fn write_bytes(
+        proc: &mut SharedProcess,
+        addr: Address,
+        blob: rhai::Blob,
+    ) -> Result<(), Box<EvalAltResult>> {
+        proc.get_mut()
+            .write_raw(addr, &blob)
+            .map_err(|e| e.as_str().into())
+    }
+
+    /// Read a batch of `[ty, addr]` requests or pointers at once, failed entries are returned as a `ReadError`.
+    #[rhai_fn(pure, return_raw, name = "read_many")]
+    pub fn read_many(
+        proc: &mut SharedProcess,
+        reqs: rhai::Array,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        let reqs = reqs
+            .into_iter()
+            .map(|req| {
+                if req.is::<NativePointer>() {
+                    let (ty, addr) = req.cast::<NativePointer>();
+                    return Ok((*ty, addr));
+                }
+
+                match req.try_cast::<rhai::Array>().as_deref() {
+                    Some([ty, addr]) if ty.is::<Type>() && addr.is::<Address>() => {
+                        Ok((ty.clone_cast::<Type>(), addr.clone_cast::<Address>()))
+                    }
+                    _ => Err("expected `[Type, Address]` or a pointer to read".into()),
+                }
+            })
+            .collect::<Result<Vec<_>, Box<EvalAltResult>>>()?;
+
+        read_many_to_dyn(proc.get_mut(), &reqs)
+    }
+
+    /// Write `val` as the native type `ty` at `addr`.
+    #[rhai_fn(pure, return_raw, name = "write")]
+    pub fn write(
+        proc: &mut SharedProcess,
+        ty: Type,
+        addr: Address,
+        val: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        write_from_dyn(proc.get_mut(), &ty, addr, val)
+    }
+
+    /// Write `val` as the pointee type of `ptr` at the pointers address.
+    #[rhai_fn(pure, return_raw, name = "write")]
+    pub fn write_ptr(
+        proc: &mut SharedProcess,
+        ptr: NativePointer,
+        val: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        write_from_dyn(proc.get_mut(), &ptr.0, ptr.1, val)
+    }
+
+    /// Address of the export `name` of the module `module`.
+    #[rhai_fn(pure, return_raw, name = "export_addr")]
+    pub fn get_export_addr(
+        proc: &mut SharedProcess,
+        module: &str,
+        name: &str,
+    ) -> Result<Address, Box<EvalAltResult>> {
+        let mi = get_module_from_name(proc, module)
+            .map_err(|e| format!("module `{}`: {}", module, e))?;
+        proc.get_mut()
+            .module_export_by_name(&mi, name)
+            .map(|export| mi.base + export.offset)
+            .map_err(|e| format!("export `{}` of `{}`: {}", name, module, e.as_str()).into())
+    }
+
+    /// Create a live view of the native type `ty` at `addr`, fields are read and written on access.
+    #[rhai_fn(pure, name = "view")]
+    pub fn view(proc: &mut SharedProcess<'static>, ty: Type, addr: Address) -> View {
+        View::new(proc.borrow().clone(), ty, addr)
+    }
+
+    /// Create a live view of the pointee of `ptr`, `proc.view(ptr)[i]` reads element `i`.
+    #[rhai_fn(pure, name = "view")]
+    pub fn view_ptr(proc: &mut SharedProcess<'static>, ptr: NativePointer) -> View {
+        View::new(proc.borrow().clone(), *ptr.0, ptr.1)
+    }
+
     #[rhai_fn(pure, get = "info")]
     pub fn get_info(proc: &mut SharedProcess) -> ProcessInfo {
         proc.borrow_mut().info().clone()
@@ -94,6 +373,159 @@ pub mod process_functions {
         pub fn get_path(mi: &mut ModuleInfo) -> String {
             mi.path.to_string()
         }
+
+        /// Architecture of the module, e.g. `x86_64`.
+        #[rhai_fn(pure, get = "arch")]
+        pub fn get_arch(mi: &mut ModuleInfo) -> String {
+            mi.arch.to_string()
+        }
+
+        /// Address of the process the module belongs to.
+        #[rhai_fn(pure, get = "parent_process")]
+        pub fn get_parent_process(mi: &mut ModuleInfo) -> Address {
+            mi.parent_process
+        }
+
+        /// Whether `addr` lies within the module.
+        #[rhai_fn(pure, name = "contains")]
+        pub fn contains(mi: &mut ModuleInfo, addr: Address) -> bool {
+            mi.base <= addr && addr < mi.base + mi.size
+        }
+
+        /// Every symbol exported by the module.
+        #[rhai_fn(pure, return_raw, name = "exports")]
+        pub fn get_exports(
+            mi: &mut ModuleInfo,
+            mut proc: SharedProcess<'static>,
+        ) -> Result<rhai::Array, Box<EvalAltResult>> {
+            let exports = proc
+                .get_mut()
+                .module_export_list(mi)
+                .map_err(|e| format!("exports of `{}`: {}", mi.name, e.as_str()))?;
+            Ok(exports
+                .into_iter()
+                .map(|export| {
+                    Dynamic::from(ModuleExport {
+                        name: export.name.to_string(),
+                        addr: mi.base + export.offset,
+                    })
+                })
+                .collect())
+        }
+
+        /// Every symbol imported by the module.
+        #[rhai_fn(pure, return_raw, name = "imports")]
+        pub fn get_imports(
+            mi: &mut ModuleInfo,
+            mut proc: SharedProcess<'static>,
+        ) -> Result<rhai::Array, Box<EvalAltResult>> {
+            let imports = proc
+                .get_mut()
+                .module_import_list(mi)
+                .map_err(|e| format!("imports of `{}`: {}", mi.name, e.as_str()))?;
+            Ok(imports
+                .into_iter()
+                .map(|import| {
+                    Dynamic::from(ModuleImport {
+                        name: import.name.to_string(),
+                        addr: mi.base + import.offset,
+                    })
+                })
+                .collect())
+        }
+
+        /// Every section of the module.
+        #[rhai_fn(pure, return_raw, name = "sections")]
+        pub fn get_sections(
+            mi: &mut ModuleInfo,
+            mut proc: SharedProcess<'static>,
+        ) -> Result<rhai::Array, Box<EvalAltResult>> {
+            let sections = proc
+                .get_mut()
+                .module_section_list(mi)
+                .map_err(|e| format!("sections of `{}`: {}", mi.name, e.as_str()))?;
+            Ok(sections.into_iter().map(Dynamic::from).collect())
+        }
+
+        /// Display the module name and base.
+        #[rhai_fn(pure, name = "to_string", name = "to_debug")]
+        pub fn to_string(mi: &mut ModuleInfo) -> String {
+            format!("ModuleInfo({} @ {:#x})", mi.name, mi.base.to_umem())
+        }
+    }
+
+    pub mod module_export_functions {
+        /// Name of the export.
+        #[rhai_fn(pure, get = "name")]
+        pub fn get_name(export: &mut ModuleExport) -> String {
+            export.name.clone()
+        }
+
+        /// Address of the export.
+        #[rhai_fn(pure, get = "addr")]
+        pub fn get_addr(export: &mut ModuleExport) -> Address {
+            export.addr
+        }
+
+        /// Display the export name and address.
+        #[rhai_fn(pure, name = "to_string", name = "to_debug")]
+        pub fn to_string(export: &mut ModuleExport) -> String {
+            format!(
+                "ModuleExport({} @ {:#x})",
+                export.name,
+                export.addr.to_umem()
+            )
+        }
+    }
+
+    pub mod module_import_functions {
+        /// Name of the import.
+        #[rhai_fn(pure, get = "name")]
+        pub fn get_name(import: &mut ModuleImport) -> String {
+            import.name.clone()
+        }
+
+        /// Module base plus the import offset reported by memflow.
+        #[rhai_fn(pure, get = "addr")]
+        pub fn get_addr(import: &mut ModuleImport) -> Address {
+            import.addr
+        }
+
+        /// Display the import name and address.
+        #[rhai_fn(pure, name = "to_string", name = "to_debug")]
+        pub fn to_string(import: &mut ModuleImport) -> String {
+            format!(
+                "ModuleImport({} @ {:#x})",
+                import.name,
+                import.addr.to_umem()
+            )
+        }
+    }
+
+    pub mod section_info_functions {
+        /// Name of the section.
+        #[rhai_fn(pure, get = "name")]
+        pub fn get_name(si: &mut SectionInfo) -> String {
+            si.name.to_string()
+        }
+
+        /// Address of the start of the section.
+        #[rhai_fn(pure, get = "base")]
+        pub fn get_base(si: &mut SectionInfo) -> Address {
+            si.base
+        }
+
+        /// Size of the section in bytes.
+        #[rhai_fn(pure, get = "size")]
+        pub fn get_size(si: &mut SectionInfo) -> rhai::INT {
+            si.size as rhai::INT
+        }
+
+        /// Display the section name and base.
+        #[rhai_fn(pure, name = "to_string", name = "to_debug")]
+        pub fn to_string(si: &mut SectionInfo) -> String {
+            format!("SectionInfo({} @ {:#x})", si.name, si.base.to_umem())
+        }
     }
 
     pub mod process_info_functions {
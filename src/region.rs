@@ -0,0 +1,188 @@
+use memflow::{
+    mem::MemoryRange,
+    prelude::{ModuleInfo, PageType, Process},
+    types::{imem, umem, Address},
+};
+
+use rhai::plugin::*;
+
+use crate::process::SharedProcess;
+
+/// Mapped range of virtual memory in a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: Address,
+    pub size: umem,
+    pub page_type: PageType,
+}
+
+impl From<MemoryRange> for MemoryRegion {
+    fn from(range: MemoryRange) -> Self {
+        Self {
+            base: range.0,
+            size: range.1,
+            page_type: range.2,
+        }
+    }
+}
+
+impl MemoryRegion {
+    /// First address past the end of the region.
+    pub fn end(&self) -> Address {
+        self.base + self.size
+    }
+
+    pub fn contains(&self, addr: Address) -> bool {
+        self.base <= addr && addr < self.end()
+    }
+
+    /// Pages of an unknown type count as executable, only `NOEXEC` rules it out.
+    pub fn executable(&self) -> bool {
+        !self.page_type.contains(PageType::NOEXEC)
+    }
+
+    pub fn writable(&self) -> bool {
+        self.page_type.contains(PageType::WRITEABLE)
+    }
+
+    /// Whether any part of the region lies within `module`.
+    pub fn in_module(&self, module: &ModuleInfo) -> bool {
+        self.base < module.base + module.size && module.base < self.end()
+    }
+}
+
+/// Virtual memory map of `proc`, ranges separated by at most `gap_size` bytes are merged if their page types match.
+pub fn memory_map(proc: &mut impl Process, gap_size: imem) -> Vec<MemoryRegion> {
+    proc.mapped_mem_vec(gap_size)
+        .into_iter()
+        .map(MemoryRegion::from)
+        .collect()
+}
+
+fn filter_regions(
+    regions: rhai::Array,
+    f: impl Fn(&MemoryRegion) -> bool,
+) -> Result<rhai::Array, Box<EvalAltResult>> {
+    regions
+        .into_iter()
+        .filter_map(|region| match region.clone().try_cast::<MemoryRegion>() {
+            Some(mr) => f(&mr).then_some(Ok(region)),
+            None => Some(Err(format!(
+                "cannot filter `{}` as a memory region",
+                region.type_name()
+            )
+            .into())),
+        })
+        .collect()
+}
+
+/// Memory region functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod region_functions {
+    /// Every mapped region of the process.
+    #[rhai_fn(pure, global, name = "memory_map")]
+    pub fn get_memory_map(proc: &mut SharedProcess) -> rhai::Array {
+        get_memory_map_gap(proc, -1)
+    }
+
+    /// Every mapped region of the process, merging regions of the same type at most `gap_size` bytes apart.
+    #[rhai_fn(pure, global, name = "memory_map")]
+    pub fn get_memory_map_gap(proc: &mut SharedProcess, gap_size: rhai::INT) -> rhai::Array {
+        memory_map(proc.get_mut(), gap_size as imem)
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    }
+
+    /// Regions in `regions` which are executable.
+    #[rhai_fn(global, return_raw, name = "executable")]
+    pub fn filter_executable(regions: rhai::Array) -> Result<rhai::Array, Box<EvalAltResult>> {
+        filter_regions(regions, MemoryRegion::executable)
+    }
+
+    /// Regions in `regions` which are writable.
+    #[rhai_fn(global, return_raw, name = "writable")]
+    pub fn filter_writable(regions: rhai::Array) -> Result<rhai::Array, Box<EvalAltResult>> {
+        filter_regions(regions, MemoryRegion::writable)
+    }
+
+    /// Regions in `regions` which overlap `module`.
+    #[rhai_fn(global, return_raw, name = "in_module")]
+    pub fn filter_in_module(
+        regions: rhai::Array,
+        module: ModuleInfo,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        filter_regions(regions, |mr| mr.in_module(&module))
+    }
+
+    /// Memory region getters and predicates.
+    pub mod memory_region_functions {
+        /// Start of the region.
+        #[rhai_fn(pure, global, get = "base")]
+        pub fn get_base(mr: &mut MemoryRegion) -> Address {
+            mr.base
+        }
+
+        /// Size of the region in bytes.
+        #[rhai_fn(pure, global, get = "size")]
+        pub fn get_size(mr: &mut MemoryRegion) -> rhai::INT {
+            mr.size as rhai::INT
+        }
+
+        /// First address past the end of the region.
+        #[rhai_fn(pure, global, get = "end")]
+        pub fn get_end(mr: &mut MemoryRegion) -> Address {
+            mr.end()
+        }
+
+        /// Raw memflow page type flags.
+        #[rhai_fn(pure, global, get = "page_type")]
+        pub fn get_page_type(mr: &mut MemoryRegion) -> rhai::INT {
+            mr.page_type.bits() as rhai::INT
+        }
+
+        /// Whether the page type of the region is unknown.
+        #[rhai_fn(pure, global, get = "unknown")]
+        pub fn get_unknown(mr: &mut MemoryRegion) -> bool {
+            mr.page_type.contains(PageType::UNKNOWN)
+        }
+
+        /// Whether `addr` lies within the region.
+        #[rhai_fn(pure, global, name = "contains")]
+        pub fn contains(mr: &mut MemoryRegion, addr: Address) -> bool {
+            mr.contains(addr)
+        }
+
+        /// Whether the region is executable, pages of an unknown type count as executable.
+        #[rhai_fn(pure, global, name = "executable")]
+        pub fn executable(mr: &mut MemoryRegion) -> bool {
+            mr.executable()
+        }
+
+        /// Whether the region is writable.
+        #[rhai_fn(pure, global, name = "writable")]
+        pub fn writable(mr: &mut MemoryRegion) -> bool {
+            mr.writable()
+        }
+
+        /// Whether any part of the region lies within `module`.
+        #[rhai_fn(pure, global, name = "in_module")]
+        pub fn in_module(mr: &mut MemoryRegion, module: ModuleInfo) -> bool {
+            mr.in_module(&module)
+        }
+
+        /// Display the range and protection of the region.
+        #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+        pub fn to_string(mr: &mut MemoryRegion) -> String {
+            format!(
+                "MemoryRegion({:#x}..{:#x} {}{})",
+                mr.base.to_umem(),
+                mr.end().to_umem(),
+                if mr.writable() { "w" } else { "-" },
+                if mr.executable() { "x" } else { "-" }
+            )
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use std::{cell::RefCell, iter::StepBy, ops::Range, rc::Rc};
+
+use memflow::{
+    prelude::{IntoProcessInstanceArcBox, ModuleInfo},
+    types::{umem, Address},
+};
+
+use rhai::plugin::*;
+
+use crate::{
+    memory::{decode_dyn, dyn_to_f64, dyn_to_int, read_pages, CHUNK_SIZE, PAGE_SIZE},
+    native::Type,
+    process::SharedProcess,
+    region::MemoryRegion,
+};
+
+/// Value scanner narrowing down the addresses of a value over successive scans.
+///
+/// Candidates are kept per block of at most `CHUNK_SIZE` readable bytes, either as a bitmap of the aligned values
+/// which are still candidates with a snapshot of the block (or only their last value if they all share it), or as a
+/// `u32` offset and the raw last value of each candidate, whichever takes less memory.
+#[derive(Clone)]
+pub struct Scanner {
+    proc: Rc<RefCell<IntoProcessInstanceArcBox<'static>>>,
+    pub ty: Type,
+    pub regions: Vec<(Address, umem)>,
+    // `None` until the first scan.
+    blocks: Option<Vec<Block>>,
+}
+
+#[derive(Clone)]
+struct Block {
+    base: Address,
+    hits: Hits,
+}
+
+#[derive(Clone)]
+enum Hits {
+    /// Aligned values of a block of `len` bytes which are set in `alive`.
+    Dense {
+        prev: Previous,
+        len: usize,
+        alive: Bitmap,
+    },
+    /// Offsets of the candidates and their last values, `ty.size()` bytes each.
+    Offsets { offsets: Vec<u32>, values: Vec<u8> },
+}
+
+/// Last values of the candidates of a dense block.
+#[derive(Clone)]
+enum Previous {
+    /// Snapshot of the whole block.
+    Snapshot(Vec<u8>),
+    /// The single value every candidate had, as after an exact scan.
+    Same(Vec<u8>),
+}
+
+impl Hits {
+    /// The cheaper representation of the candidates `alive` in the snapshot `buf` at `base`, `None` if there are
+    /// none.
+    fn pack(base: Address, buf: Vec<u8>, size: usize, alive: Bitmap) -> Option<Self> {
+        let count = alive.count();
+        if count == 0 {
+            return None;
+        }
+
+        let mut values = aligned_offsets(base, buf.len(), size)
+            .enumerate()
+            .filter(|(slot, _)| alive.get(*slot))
+            .map(|(_, off)| &buf[off..off + size]);
+        let first = values.next().unwrap_or_default();
+        let same = values.all(|val| val == first).then(|| first.to_vec());
+
+        // Offsets only pay off once few enough candidates are left.
+        let dense = alive.bytes() + same.as_ref().map_or(buf.len(), Vec::len);
+        if count * (4 + size) < dense {
+            let mut offsets = Vec::with_capacity(count);
+            let mut values = Vec::with_capacity(count * size);
+            for (slot, off) in aligned_offsets(base, buf.len(), size).enumerate() {
+                if alive.get(slot) {
+                    offsets.push(off as u32);
+                    values.extend_from_slice(&buf[off..off + size]);
+                }
+            }
+            return Some(Self::Offsets { offsets, values });
+        }
+
+        let len = buf.len();
+        let prev = match same {
+            Some(val) => Previous::Same(val),
+            None => Previous::Snapshot(buf),
+        };
+        Some(Self::Dense { prev, len, alive })
+    }
+}
+
+/// One bit per aligned value of a block.
+#[derive(Clone)]
+struct Bitmap(Vec<u64>);
+
+impl Bitmap {
+    fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self.0[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn set(&mut self, idx: usize) {
+        self.0[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    fn bytes(&self) -> usize {
+        self.0.len() * 8
+    }
+}
+
+/// Comparison for `Scanner::next_scan`.
+#[derive(Debug, Clone)]
+pub enum ScanOp {
+    Exact(Dynamic),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Between(Dynamic, Dynamic),
+}
+
+impl ScanOp {
+    /// Build the comparison `op` from its name and values.
+    pub fn new(op: &str, mut args: Vec<Dynamic>) -> Result<Self, Box<EvalAltResult>> {
+        let expected = match op {
+            "exact" => 1,
+            "between" => 2,
+            "changed" | "unchanged" | "increased" | "decreased" => 0,
+            _ => return Err(format!("unknown scan `{}`", op).into()),
+        };
+        if args.len() != expected {
+            return Err(format!(
+                "scan `{}` takes {} value(s) but {} were given",
+                op,
+                expected,
+                args.len()
+            )
+            .into());
+        }
+
+        Ok(match op {
+            "exact" => Self::Exact(args.remove(0)),
+            "between" => Self::Between(args.remove(0), args.remove(0)),
+            "changed" => Self::Changed,
+            "unchanged" => Self::Unchanged,
+            "increased" => Self::Increased,
+            _ => Self::Decreased,
+        })
+    }
+}
+
+// Decoded values are compared as integers, or as floats for floating point types.
+#[derive(PartialEq, PartialOrd)]
+enum Num {
+    Int(i128),
+    Float(f64),
+}
+
+fn to_num(ty: &Type, val: &Dynamic) -> Result<Num, Box<EvalAltResult>> {
+    match ty {
+        Type::Fp32 | Type::Fp64 => dyn_to_f64(ty, val).map(Num::Float),
+        _ => dyn_to_int(ty, val).map(Num::Int),
+    }
+}
+
+/// Offset of the first value of `size` bytes after `base` which is aligned to its size.
+fn first_aligned(base: Address, size: usize) -> usize {
+    (size - (base.to_umem() % size as umem) as usize) % size
+}
+
+/// Offsets of every value of `size` bytes in `len` bytes at `base` which is aligned to its size.
+fn aligned_offsets(base: Address, len: usize, size: usize) -> StepBy<Range<usize>> {
+    (first_aligned(base, size)..len.saturating_sub(size - 1)).step_by(size)
+}
+
+impl Scanner {
+    pub fn new(
+        proc: IntoProcessInstanceArcBox<'static>,
+        ty: Type,
+        regions: Vec<(Address, umem)>,
+    ) -> Result<Self, Box<EvalAltResult>> {
+        match ty {
+            Type::Pointer32(_)
+            | Type::Pointer64(_)
+            | Type::String(_)
+            | Type::WideString(_)
+            | Type::Struct(_)
+            | Type::Collection(_, _)
+            | Type::DynCollection(_, _)
+            | Type::Bitfield(_) => Err(format!("cannot scan for `{}` values", ty.name()).into()),
+            // Flags decode to an array of names, which cannot be compared.
+            Type::Enum(e) if e.flags => {
+                Err(format!("cannot scan for `{}` flags values", e.name).into())
+            }
+            _ => Ok(Self {
+                proc: Rc::new(RefCell::new(proc)),
+                ty,
+                regions,
+                blocks: None,
+            }),
+        }
+    }
+
+    /// Scan every region for `value`, or for any value if `value` is `None`.
+    pub fn first_scan(&mut self, value: Option<Dynamic>) -> Result<(), Box<EvalAltResult>> {
+        let target = value.map(|val| to_num(&self.ty, &val)).transpose()?;
+        let size = self.ty.size() as usize;
+
+        let mut blocks = vec![];
+        let mut finish = |base: Address, buf: Vec<u8>| -> Result<(), Box<EvalAltResult>> {
+            let mut alive = Bitmap::new(aligned_offsets(base, buf.len(), size).len());
+            for (slot, off) in aligned_offsets(base, buf.len(), size).enumerate() {
+                let hit = match &target {
+                    None => true,
+                    Some(target) => {
+                        to_num(&self.ty, &decode_dyn(&self.ty, &buf[off..off + size])?)? == *target
+                    }
+                };
+                if hit {
+                    alive.set(slot);
+                }
+            }
+            if let Some(hits) = Hits::pack(base, buf, size, alive) {
+                blocks.push(Block { base, hits });
+            }
+            Ok(())
+        };
+
+        let mut proc = self.proc.borrow_mut();
+        for (start, len) in &self.regions {
+            // Contiguous readable memory is gathered into blocks of at most `CHUNK_SIZE` bytes.
+            let mut block: Option<(Address, Vec<u8>)> = None;
+            read_pages(&mut *proc, *start, *len, |addr, _, page| {
+                match (page, &mut block) {
+                    (Some(page), Some((base, buf)))
+                        if *base + buf.len() == addr && ((buf.len() as umem) < CHUNK_SIZE) =>
+                    {
+                        buf.extend_from_slice(&page)
+                    }
+                    (Some(page), _) => {
+                        if let Some((base, buf)) = block.replace((addr, page)) {
+                            finish(base, buf)?;
+                        }
+                    }
+                    (None, _) => {
+                        if let Some((base, buf)) = block.take() {
+                            finish(base, buf)?;
+                        }
+                    }
+                }
+                Ok(true)
+            })?;
+            if let Some((base, buf)) = block {
+                finish(base, buf)?;
+            }
+        }
+
+        self.blocks = Some(blocks);
+        Ok(())
+    }
+
+    /// Narrow down the candidates of the previous scan to the ones matching `op`, unreadable candidates are dropped.
+    ///
+    /// The previous candidates are released block by block while scanning, a failed scan discards all of them.
+    pub fn next_scan(&mut self, op: ScanOp) -> Result<(), Box<EvalAltResult>> {
+        if self.blocks.is_none() {
+            return Err("`next_scan` requires a first scan".into());
+        }
+        let size = self.ty.size() as usize;
+        let ty = &self.ty;
+
+        let op = match op {
+            ScanOp::Exact(val) => Cmp::Exact(to_num(ty, &val)?),
+            ScanOp::Between(lo, hi) => Cmp::Between(to_num(ty, &lo)?, to_num(ty, &hi)?),
+            ScanOp::Changed => Cmp::Changed,
+            ScanOp::Unchanged => Cmp::Unchanged,
+            ScanOp::Increased => Cmp::Increased,
+            ScanOp::Decreased => Cmp::Decreased,
+        };
+        let matches = |prev: &[u8], cur: &[u8]| -> Result<bool, Box<EvalAltResult>> {
+            let cur = to_num(ty, &decode_dyn(ty, cur)?)?;
+            let prev = || -> Result<Num, Box<EvalAltResult>> { to_num(ty, &decode_dyn(ty, prev)?) };
+            Ok(match &op {
+                Cmp::Exact(val) => cur == *val,
+                Cmp::Between(lo, hi) => *lo <= cur && cur <= *hi,
+                Cmp::Changed => cur != prev()?,
+                Cmp::Unchanged => cur == prev()?,
+                Cmp::Increased => cur > prev()?,
+                Cmp::Decreased => cur < prev()?,
+            })
+        };
+
+        let mut proc = self.proc.borrow_mut();
+        let mut next = vec![];
+        for block in self.blocks.take().into_iter().flatten() {
+            let block_base = block.base;
+            let span = match &block.hits {
+                Hits::Dense { len, .. } => *len,
+                // Blocks without candidates are dropped, so there always is a last offset.
+                Hits::Offsets { offsets, .. } => *offsets.last().unwrap() as usize + size,
+            };
+
+            // Unreadable pages are left zeroed and their candidates skipped.
+            let mut cur = Vec::with_capacity(span);
+            let mut readable = vec![];
+            read_pages(&mut *proc, block.base, span as umem, |_, len, page| {
+                readable.push(page.is_some());
+                cur.extend(page.unwrap_or_else(|| vec![0; len]));
+                Ok(true)
+            })?;
+            let first_page = block.base.to_umem() / PAGE_SIZE;
+            let first = first_aligned(block.base, size);
+
+            let mut alive = Bitmap::new(aligned_offsets(block.base, span, size).len());
+            let mut check = |off: usize, prev: &[u8]| -> Result<(), Box<EvalAltResult>> {
+                let page = ((block.base.to_umem() + off as umem) / PAGE_SIZE - first_page) as usize;
+                if readable[page] && matches(prev, &cur[off..off + size])? {
+                    alive.set((off - first) / size);
+                }
+                Ok(())
+            };
+            match &block.hits {
+                Hits::Dense {
+                    prev,
+                    len,
+                    alive: prev_alive,
+                } => {
+                    for (slot, off) in aligned_offsets(block.base, *len, size).enumerate() {
+                        if prev_alive.get(slot) {
+                            let prev = match prev {
+                                Previous::Snapshot(buf) => &buf[off..off + size],
+                                Previous::Same(val) => val.as_slice(),
+                            };
+                            check(off, prev)?;
+                        }
+                    }
+                }
+                Hits::Offsets { offsets, values } => {
+                    for (off, prev) in offsets.iter().zip(values.chunks_exact(size)) {
+                        check(*off as usize, prev)?;
+                    }
+                }
+            }
+            drop(block);
+
+            if let Some(hits) = Hits::pack(block_base, cur, size, alive) {
+                next.push(Block {
+                    base: block_base,
+                    hits,
+                });
+            }
+        }
+
+        self.blocks = Some(next);
+        Ok(())
+    }
+
+    /// Addresses of the remaining candidates, at most `limit` of them.
+    pub fn results(&self, limit: usize) -> Vec<Address> {
+        let size = self.ty.size() as usize;
+        self.blocks
+            .iter()
+            .flatten()
+            .flat_map(|block| {
+                let offsets: Box<dyn Iterator<Item = usize>> = match &block.hits {
+                    Hits::Dense { len, alive, .. } => Box::new(
+                        aligned_offsets(block.base, *len, size)
+                            .enumerate()
+                            .filter(|(slot, _)| alive.get(*slot))
+                            .map(|(_, off)| off),
+                    ),
+                    Hits::Offsets { offsets, .. } => {
+                        Box::new(offsets.iter().map(|off| *off as usize))
+                    }
+                };
+                offsets.map(move |off| block.base + off)
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// Number of remaining candidates.
+    pub fn count(&self) -> usize {
+        self.blocks
+            .iter()
+            .flatten()
+            .map(|block| match &block.hits {
+                Hits::Dense { alive, .. } => alive.count(),
+                Hits::Offsets { offsets, .. } => offsets.len(),
+            })
+            .sum()
+    }
+}
+
+enum Cmp {
+    Exact(Num),
+    Between(Num, Num),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+/// Scan region from a `MemoryRegion`, a `ModuleInfo` or an `[addr, size]` pair.
+fn region_from_dyn(val: Dynamic) -> Result<(Address, umem), Box<EvalAltResult>> {
+    if val.is::<MemoryRegion>() {
+        let mr = val.cast::<MemoryRegion>();
+        return Ok((mr.base, mr.size));
+    }
+    if val.is::<ModuleInfo>() {
+        let mi = val.cast::<ModuleInfo>();
+        return Ok((mi.base, mi.size));
+    }
+
+    match val.try_cast::<rhai::Array>().as_deref() {
+        Some([addr, size]) if addr.is::<Address>() => match size.as_int().map(umem::try_from) {
+            Ok(Ok(size)) => Ok((addr.clone_cast::<Address>(), size)),
+            _ => Err("region size must be a positive number".into()),
+        },
+        _ => Err("regions must be memory regions, modules or `[addr, size]` pairs".into()),
+    }
+}
+
+/// Scanner functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod scanner_functions {
+    /// Create a scanner for `ty` values in `regions`, each region is a memory region, a module or an `[addr, size]`
+    /// pair.
+    #[rhai_fn(pure, global, return_raw, name = "scanner")]
+    pub fn scanner(
+        proc: &mut SharedProcess<'static>,
+        ty: Type,
+        regions: rhai::Array,
+    ) -> Result<Scanner, Box<EvalAltResult>> {
+        let regions = regions
+            .into_iter()
+            .map(region_from_dyn)
+            .collect::<Result<_, _>>()?;
+        Scanner::new(proc.borrow().clone(), ty, regions)
+    }
+
+    /// Scan for `value`, discarding any previous results.
+    #[rhai_fn(global, return_raw, name = "first_scan")]
+    pub fn first_scan(scanner: &mut Scanner, value: Dynamic) -> Result<(), Box<EvalAltResult>> {
+        scanner.first_scan(Some(value))
+    }
+
+    /// Scan for any value, discarding any previous results.
+    #[rhai_fn(global, return_raw, name = "first_scan_unknown")]
+    pub fn first_scan_unknown(scanner: &mut Scanner) -> Result<(), Box<EvalAltResult>> {
+        scanner.first_scan(None)
+    }
+
+    /// Keep the results which `changed`, are `unchanged`, `increased` or `decreased`.
+    #[rhai_fn(global, return_raw, name = "next_scan")]
+    pub fn next_scan(scanner: &mut Scanner, op: &str) -> Result<(), Box<EvalAltResult>> {
+        scanner.next_scan(ScanOp::new(op, vec![])?)
+    }
+
+    /// Keep the results which are `exact`ly `value`.
+    #[rhai_fn(global, return_raw, name = "next_scan")]
+    pub fn next_scan_value(
+        scanner: &mut Scanner,
+        op: &str,
+        value: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        scanner.next_scan(ScanOp::new(op, vec![value])?)
+    }
+
+    /// Keep the results `between` `lo` and `hi` inclusive.
+    #[rhai_fn(global, return_raw, name = "next_scan")]
+    pub fn next_scan_range(
+        scanner: &mut Scanner,
+        op: &str,
+        lo: Dynamic,
+        hi: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        scanner.next_scan(ScanOp::new(op, vec![lo, hi])?)
+    }
+
+    /// Addresses of every result.
+    #[rhai_fn(pure, global, name = "results")]
+    pub fn results(scanner: &mut Scanner) -> rhai::Array {
+        scanner
+            .results(usize::MAX)
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    }
+
+    /// Addresses of the first `limit` results.
+    #[rhai_fn(pure, global, name = "results")]
+    pub fn results_limit(scanner: &mut Scanner, limit: rhai::INT) -> rhai::Array {
+        scanner
+            .results(usize::try_from(limit).unwrap_or(0))
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    }
+
+    /// Number of results.
+    #[rhai_fn(pure, global, get = "count")]
+    pub fn get_count(scanner: &mut Scanner) -> rhai::INT {
+        scanner.count() as rhai::INT
+    }
+
+    /// Scanned type.
+    #[rhai_fn(pure, global, get = "native_type")]
+    pub fn get_type(scanner: &mut Scanner) -> Type {
+        scanner.ty.clone()
+    }
+
+    /// Display the scanned type and number of results.
+    #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+    pub fn to_string(scanner: &mut Scanner) -> String {
+        format!(
+            "Scanner({}, {} results)",
+            scanner.ty.name(),
+            scanner.count()
+        )
+    }
+}
@@ -0,0 +1,204 @@
+use std::{cell::RefCell, rc::Rc};
+
+use memflow::{prelude::IntoProcessInstanceArcBox, types::Address};
+
+use rhai::plugin::*;
+
+use crate::{
+    memory::{element_offset, offset_addr, read_to_dyn, resolve_type, write_from_dyn},
+    native::Type,
+};
+
+/// Live view of a native type in process memory, fields are only read or written when accessed.
+#[derive(Clone)]
+pub struct View {
+    // Shared between a view and all of its nested views.
+    proc: Rc<RefCell<IntoProcessInstanceArcBox<'static>>>,
+    pub ty: Type,
+    pub addr: Address,
+}
+
+impl View {
+    pub fn new(proc: IntoProcessInstanceArcBox<'static>, ty: Type, addr: Address) -> Self {
+        Self {
+            proc: Rc::new(RefCell::new(proc)),
+            ty,
+            addr,
+        }
+    }
+
+    fn nested(&self, ty: Type, addr: Address) -> Self {
+        Self {
+            proc: self.proc.clone(),
+            ty,
+            addr,
+        }
+    }
+
+    /// Read the entire viewed value.
+    pub fn read(&self) -> Result<Dynamic, Box<EvalAltResult>> {
+        read_to_dyn(&mut *self.proc.borrow_mut(), &self.ty, self.addr)
+    }
+
+    /// Write the entire viewed value.
+    pub fn write(&self, val: Dynamic) -> Result<(), Box<EvalAltResult>> {
+        write_from_dyn(&mut *self.proc.borrow_mut(), &self.ty, self.addr, val)
+    }
+
+    /// Type and address of the field `name`.
+    pub fn field(&self, name: &str) -> Result<(Type, Address), Box<EvalAltResult>> {
+        // Lengths taken from other fields are read now, so the field has a fixed type.
+        let resolved;
+        let ty = match self.ty.is_dynamic() {
+            true => {
+                resolved = resolve_type(&mut *self.proc.borrow_mut(), &self.ty, self.addr)?;
+                &resolved
+            }
+            false => &self.ty,
+        };
+        match ty {
+            Type::Struct(n) => n
+                .fields()
+                .find(|(_, nf)| nf.name == name)
+                .map(|(offset, nf)| (nf.ty.clone(), self.addr + offset))
+                .ok_or_else(|| format!("no field `{}` in view", name).into()),
+            ty => Err(format!("cannot access field `{}` of `{}`", name, ty.name()).into()),
+        }
+    }
+
+    /// Type and address of the element at `index`, any view but a collection is the first element of an array like
+    /// a C pointer.
+    pub fn element(&self, index: rhai::INT) -> Result<(Type, Address), Box<EvalAltResult>> {
+        match &self.ty {
+            Type::Collection(ty, num) => match u32::try_from(index) {
+                Ok(index) if index < *num => Ok((
+                    (**ty).clone(),
+                    offset_addr(self.addr, element_offset(ty, index.into())?)?,
+                )),
+                _ => Err(format!(
+                    "index {} out of bounds for `Collection` of length {}",
+                    index, num
+                )
+                .into()),
+            },
+            ty if ty.is_dynamic() => {
+                Err(format!("cannot index into `{}` of dynamic size", ty.name()).into())
+            }
+            ty => Ok((
+                ty.clone(),
+                offset_addr(self.addr, element_offset(ty, index)?)?,
+            )),
+        }
+    }
+
+    /// Structs and collections are returned as nested views, pointers as views of their pointee and anything else is read.
+    pub fn member(&self, ty: Type, addr: Address) -> Result<Dynamic, Box<EvalAltResult>> {
+        match ty {
+            Type::Struct(_) | Type::Collection(_, _) => Ok(Dynamic::from(self.nested(ty, addr))),
+            Type::Pointer32(_) | Type::Pointer64(_) => {
+                let (pty, paddr) = read_to_dyn(&mut *self.proc.borrow_mut(), &ty, addr)?
+                    .cast::<crate::memory::NativePointer>();
+                Ok(Dynamic::from(self.nested(*pty, paddr)))
+            }
+            _ => read_to_dyn(&mut *self.proc.borrow_mut(), &ty, addr),
+        }
+    }
+
+    /// Write `val` to the member, views assigned to a struct or collection have their contents copied and views
+    /// assigned to a pointer are pointed to.
+    pub fn set_member(
+        &self,
+        ty: Type,
+        addr: Address,
+        val: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        if !val.is::<View>() {
+            return write_from_dyn(&mut *self.proc.borrow_mut(), &ty, addr, val);
+        }
+
+        let view = val.cast::<View>();
+        match ty {
+            Type::Pointer32(_) | Type::Pointer64(_) => write_from_dyn(
+                &mut *self.proc.borrow_mut(),
+                &ty,
+                addr,
+                Dynamic::from(view.addr),
+            ),
+            // Assigning a nested view back to where it came from (i.e. `view.pos.x = 1.0`) is a no-op.
+            _ if view.ty == ty && view.addr == addr => Ok(()),
+            _ => {
+                let val = view.read()?;
+                write_from_dyn(&mut *self.proc.borrow_mut(), &ty, addr, val)
+            }
+        }
+    }
+}
+
+/// View functions.
+#[export_module]
+#[allow(dead_code)]
+#[warn(missing_docs)]
+pub mod view_functions {
+    /// Read the field `name`.
+    #[rhai_fn(pure, global, return_raw, index_get)]
+    pub fn get_field(view: &mut View, name: &str) -> Result<Dynamic, Box<EvalAltResult>> {
+        let (ty, addr) = view.field(name)?;
+        view.member(ty, addr)
+    }
+
+    /// Write the field `name`.
+    #[rhai_fn(global, return_raw, index_set)]
+    pub fn set_field(view: &mut View, name: &str, val: Dynamic) -> Result<(), Box<EvalAltResult>> {
+        let (ty, addr) = view.field(name)?;
+        view.set_member(ty, addr, val)
+    }
+
+    /// Read the element at `index`.
+    #[rhai_fn(pure, global, return_raw, index_get)]
+    pub fn get_element(view: &mut View, index: rhai::INT) -> Result<Dynamic, Box<EvalAltResult>> {
+        let (ty, addr) = view.element(index)?;
+        view.member(ty, addr)
+    }
+
+    /// Write the element at `index`.
+    #[rhai_fn(global, return_raw, index_set)]
+    pub fn set_element(
+        view: &mut View,
+        index: rhai::INT,
+        val: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let (ty, addr) = view.element(index)?;
+        view.set_member(ty, addr, val)
+    }
+
+    /// Read the entire viewed value.
+    #[rhai_fn(pure, global, return_raw, name = "read")]
+    pub fn read(view: &mut View) -> Result<Dynamic, Box<EvalAltResult>> {
+        view.read()
+    }
+
+    /// Write the entire viewed value.
+    #[rhai_fn(global, return_raw, name = "write")]
+    pub fn write(view: &mut View, val: Dynamic) -> Result<(), Box<EvalAltResult>> {
+        view.write(val)
+    }
+
+    // Methods instead of getters so they never shadow a field of the same name.
+    /// Return the viewed address.
+    #[rhai_fn(pure, global, name = "addr")]
+    pub fn get_addr(view: &mut View) -> Address {
+        view.addr
+    }
+
+    /// Return the viewed type.
+    #[rhai_fn(pure, global, name = "native_type")]
+    pub fn get_type(view: &mut View) -> Type {
+        view.ty.clone()
+    }
+
+    /// Format as the viewed type and address.
+    #[rhai_fn(pure, global, name = "to_string", name = "to_debug")]
+    pub fn to_string(view: &mut View) -> String {
+        format!("View({} @ {:#x})", view.ty.name(), view.addr.to_umem())
+    }
+}
@@ -1,4 +1,6 @@
+use memflow::mem::mem_data::{ReadRawMemOps, WriteRawMemOps};
 use memflow::prelude::phys_mem::PhysicalMemoryView;
+use memflow::prelude::MemoryViewMetadata;
 use memflow::types::{size, Address};
 use memflow::{
     dummy::*,
@@ -97,7 +99,366 @@ fn test_memory() -> Result<(), Box<EvalAltResult>> {
             == Ok(66 as rhai::INT)
     );
 
-    // TODO: Add testing for the type casts.
+    // Write float works
+    assert_eq!(
+        engine.eval_with_scope::<rhai::FLOAT>(
+            &mut scope,
+            r#"MEMORY.write(Fp32, addr(128), 1.5); MEMORY.read(Fp32, addr(128))"#
+        )?,
+        1.5
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::FLOAT>(
+            &mut scope,
+            r#"MEMORY.write(Fp64, addr(128), 3); MEMORY.read(Fp64, addr(128))"#
+        )?,
+        3.0
+    );
+
+    // Floats are truncated toward zero
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"MEMORY.write(Int32, addr(128), -1.9); MEMORY.read(Int32, addr(128))"#
+        )?,
+        -1
+    );
+
+    // Bools are written as integers
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"MEMORY.write(UInt8, addr(128), true); MEMORY.read(UInt8, addr(128))"#
+        )?,
+        1
+    );
+
+    // Write address works
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"MEMORY.write(Address64, addr(128), addr(0x1234)); MEMORY.read(Address64, addr(128))"#
+        )?,
+        Address::from(0x1234)
+    );
+
+    // Writing an address to a pointer replaces the pointer, anything else writes to the pointee
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"
+            MEMORY.write(Pointer32(Int32), addr(128), ptr(Int32, addr(144)));
+            MEMORY.write(Pointer32(Int32), addr(128), 99);
+            MEMORY.read(Int32, MEMORY.read(Pointer32(Int32), addr(128)).addr)
+            "#
+        )?,
+        99
+    );
+
+    // UInt64 is lossless
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(
+            &mut scope,
+            r#"
+            MEMORY.write(UInt64, addr(128), u64("0xFFFFFFFFFFFFFFF0"));
+            let num = MEMORY.read(UInt64, addr(128));
+            MEMORY.write(UInt64, addr(136), num + 0xF);
+            MEMORY.read(UInt64, addr(136)).to_hex()
+            "#
+        )?,
+        "0xffffffffffffffff"
+    );
+    assert!(engine.eval_with_scope::<bool>(
+        &mut scope,
+        r#"let num = MEMORY.read(UInt64, addr(136)); num > 0 && num == u64("18446744073709551615") && num >> 60 == 0xF"#
+    )?);
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.read(UInt64, addr(136)) + 1"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.read(UInt64, addr(136)).to_int()"#)
+        .is_err());
+
+    // Signed, bool and char primitives work
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"
+            MEMORY.write(Int8, addr(128), -128);
+            MEMORY.write(Int16, addr(130), -300);
+            MEMORY.read(Int8, addr(128)) + MEMORY.read(Int16, addr(130))
+            "#
+        )?,
+        -428
+    );
+    assert!(engine.eval_with_scope::<bool>(
+        &mut scope,
+        r#"
+        MEMORY.write(Bool8, addr(128), true);
+        MEMORY.write(Bool32, addr(132), 0);
+        MEMORY.read(Bool8, addr(128)) && !MEMORY.read(Bool32, addr(132))
+        "#
+    )?);
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(
+            &mut scope,
+            r#"
+            MEMORY.write(Char8, addr(128), 'é');
+            MEMORY.write(Char16, addr(130), 'Ж');
+            `${MEMORY.read(Char8, addr(128))}${MEMORY.read(Char16, addr(130))}`
+            "#
+        )?,
+        "éЖ"
+    );
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.write(Char8, addr(128), 'Ж')"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.write(Int8, addr(128), 128)"#)
+        .is_err());
+
+    // Out of range values are errors
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.write(UInt8, addr(128), 256)"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.write(UInt32, addr(128), -1)"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"MEMORY.write(Fp32, addr(128), 1e300)"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"MEMORY.write(Address32, addr(128), addr(0x100000000))"#
+        )
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_address() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Arithmetic works
+    assert_eq!(
+        engine.eval::<Address>(r#"addr(0x1000) + -16"#)?,
+        Address::from(0xff0)
+    );
+    assert_eq!(
+        engine.eval::<Address>(r#"addr(0x1000) - 0x10"#)?,
+        Address::from(0xff0)
+    );
+    assert_eq!(
+        engine.eval::<rhai::INT>(r#"addr(0x1000) - addr(0x1010)"#)?,
+        -0x10
+    );
+    assert!(engine.eval::<()>(r#"addr(0x10) - 0x20"#).is_err());
+    assert!(engine.eval::<()>(r#"addr(-1)"#).is_err());
+
+    // Comparison works
+    assert!(engine.eval::<bool>(
+        r#"addr(1) < addr(2) && addr(2) > addr(1) && addr(2) <= addr(2) && addr(2) >= addr(1) && addr(1) != addr(2)"#
+    )?);
+
+    // Bitwise operators work
+    assert_eq!(
+        engine.eval::<Address>(r#"(addr(0x1234) & 0xFF0) | 0x1"#)?,
+        Address::from(0x231)
+    );
+    assert_eq!(
+        engine.eval::<Address>(r#"(addr(0x1234) >> 4) << 8"#)?,
+        Address::from(0x12300)
+    );
+
+    // Alignment works
+    assert_eq!(
+        engine.eval::<Address>(r#"addr(0x1001).align_up(0x1000)"#)?,
+        Address::from(0x2000)
+    );
+    assert_eq!(
+        engine.eval::<Address>(r#"addr(0x1fff).align_down(0x1000)"#)?,
+        Address::from(0x1000)
+    );
+    assert!(engine.eval::<()>(r#"addr(0x1001).align_up(3)"#).is_err());
+
+    // Parsing and formatting works
+    assert_eq!(
+        engine.eval::<Address>(r#"addr("0x7ff6`12345678")"#)?,
+        Address::from(0x7ff6_1234_5678_u64)
+    );
+    assert!(engine.eval::<()>(r#"addr("0xzz")"#).is_err());
+    assert_eq!(
+        engine.eval::<ImmutableString>(r#"addr(0x7ff6).to_string()"#)?,
+        "0x7ff6"
+    );
+    assert!(engine.eval::<bool>(r#"addr(0).is_null() && !addr(1).is_null()"#)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_pointer() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Arithmetic is scaled by the pointee size
+    assert!(engine.eval::<bool>(r#"ptr(Int32, addr(0x1000)) + 2 == addr(0x1008)"#)?);
+    assert!(engine.eval::<bool>(r#"ptr(Int64, addr(0x1000)) - 2 == addr(0xff0)"#)?);
+    assert!(engine.eval::<bool>(
+        r#"native Player { hp: Fp32, ^ 8, id: UInt32 }; ptr(Player, addr(0x1000)).offset(3) == addr(0x1030)"#
+    )?);
+    // Pointers never equal negative numbers.
+    assert!(engine.eval::<bool>(r#"ptr(UInt16, addr(8)) == 8"#)?);
+    assert!(!engine.eval::<bool>(r#"ptr(UInt16, addr(8)) == -8"#)?);
+    assert_eq!(
+        engine.eval::<rhai::INT>(r#"ptr(UInt16, addr(0x1000)) - ptr(UInt16, addr(0x1010))"#)?,
+        -8
+    );
+    assert!(engine
+        .eval::<()>(r#"ptr(UInt16, addr(0x1000)) - ptr(UInt32, addr(0x1010))"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"ptr(UInt16, addr(0x1000)) - ptr(UInt16, addr(0x1001))"#)
+        .is_err());
+
+    // Byte offsets ignore the pointee size
+    assert!(engine.eval::<bool>(r#"ptr(Int32, addr(0x1000)).byte_offset(2) == addr(0x1002)"#)?);
+    assert!(engine.eval::<bool>(r#"ptr(Int32, addr(0x1000)).byte_offset(2) == Int32"#)?);
+
+    Ok(())
+}
+
+/// Memory view which counts the number of reads made through it.
+struct CountingMemory<T> {
+    mem: T,
+    reads: usize,
+}
+
+impl<T: MemoryView> MemoryView for CountingMemory<T> {
+    fn read_raw_iter(&mut self, data: ReadRawMemOps) -> memflow::error::Result<()> {
+        self.reads += 1;
+        self.mem.read_raw_iter(data)
+    }
+
+    fn write_raw_iter(&mut self, data: WriteRawMemOps) -> memflow::error::Result<()> {
+        self.mem.write_raw_iter(data)
+    }
+
+    fn metadata(&self) -> MemoryViewMetadata {
+        self.mem.metadata()
+    }
+}
+
+#[test]
+fn test_read_coalesced() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    let players = engine.eval::<Type>(
+        r#"
+        native Vec2 { x: Fp32, y: Fp32 };
+        native Player { health: Int32, pos: Vec2, name: String(16), target: Pointer64(Vec2) };
+        Collection(Player, 64)
+        "#,
+    )?;
+
+    let mut mem = CountingMemory {
+        mem: DummyMemory::new(size::mb(1)).into_phys_view(),
+        reads: 0,
+    };
+    mem.write::<i32>(Address::from(36 * 63), &7).unwrap();
+    mem.write::<[u8]>(Address::from(36 * 63 + 12), "player".as_bytes())
+        .unwrap();
+
+    let arr = read_to_dyn(&mut mem, &players, Address::null())?.into_array()?;
+
+    // The entire collection, strings and pointers included, is fetched with a single read.
+    assert_eq!(mem.reads, 1);
+    assert_eq!(arr.len(), 64);
+    let player = arr[63].read_lock::<rhai::Map>().unwrap();
+    assert_eq!(player["health"].as_int()?, 7);
+    assert_eq!(player["name"].clone().into_string()?, "player");
+
+    // Types too large to read at once are refused before anything is read.
+    let huge = Type::Collection(Box::new(Type::UInt64), 0x7fffffff);
+    assert!(read_to_dyn(&mut mem, &huge, Address::null()).is_err());
+    let huge = Type::Collection(Box::new(Type::UInt8), 0x7fffffff);
+    assert!(read_to_dyn(&mut mem, &huge, Address::null()).is_err());
+    assert_eq!(mem.reads, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_blob() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Encode works
+    assert_eq!(
+        engine.eval::<rhai::Blob>(r#"encode(UInt32, 0x11223344)"#)?,
+        0x11223344_u32.to_ne_bytes().to_vec()
+    );
+    assert_eq!(
+        engine.eval::<rhai::Blob>(
+            r#"native Test { a: UInt8, ^ 1, b: String(2) }; encode(Test, #{ a: 1, b: "x" })"#
+        )?,
+        vec![1, 0, b'x', 0]
+    );
+
+    // Decode works
+    assert_eq!(
+        engine.eval::<rhai::INT>(
+            r#"let blob = encode(Collection(Int16, 3), [1, -2, 3]); decode(Int16, blob, 2)"#
+        )?,
+        -2
+    );
+    assert_eq!(
+        engine.eval::<ImmutableString>(
+            r#"
+            native Test { a: UInt8, ^ 1, b: String(2) };
+            let test = decode(Test, encode(Test, #{ a: 1, b: "x" }));
+            `${test.a}${test.b}`
+            "#
+        )?,
+        "1x"
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval::<()>(r#"decode(UInt32, encode(UInt16, 1))"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"decode(UInt8, encode(UInt16, 1), 2)"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"decode(UInt8, encode(UInt16, 1), -1)"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"encode(Collection(UInt64, 0x7FFFFFFF), [])"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"encode(String(0x7FFFFFFF), "")"#)
+        .is_err());
+    assert_eq!(
+        engine.eval::<rhai::INT>(r#"Collection(UInt64, 0x7FFFFFFF).size"#)?,
+        rhai::INT::from(u32::MAX)
+    );
 
     Ok(())
 }
@@ -3,7 +3,7 @@ use std::collections::BTreeMap;
 use rhai::{packages::Package, Engine, EvalAltResult};
 
 use rhai_memflow::{
-    native::{Field, Struct, Type},
+    native::{Bitfield, Bits, Enum, Field, Struct, Type},
     MemflowPackage,
 };
 
@@ -19,22 +19,22 @@ fn test_native_syntax() -> Result<(), Box<EvalAltResult>> {
     assert_eq!(
         engine.eval::<Type>(r#"native Test { ^ 30, field: Int32, field2: UInt16 }; Test"#)?,
         {
-            let mut fields: BTreeMap<u32, Field> = BTreeMap::new();
+            let mut fields: BTreeMap<u32, Vec<Field>> = BTreeMap::new();
 
             fields.insert(
                 30,
-                Field {
+                vec![Field {
                     name: "field".to_string(),
                     ty: Type::Int32,
-                },
+                }],
             );
 
             fields.insert(
                 34,
-                Field {
+                vec![Field {
                     name: "field2".to_string(),
                     ty: Type::UInt16,
-                },
+                }],
             );
 
             Type::Struct(Struct::new(fields))
@@ -112,7 +112,7 @@ fn test_custom_native() -> Result<(), Box<EvalAltResult>> {
         Type::Struct(n) => assert_eq!(n.get_field(30).unwrap(), {
             let mut custom_native = Struct::new(BTreeMap::new());
 
-            custom_native.0.insert(
+            custom_native.insert(
                 0,
                 Field {
                     name: "f".into(),
@@ -140,6 +140,433 @@ fn test_native_type() -> Result<(), Box<EvalAltResult>> {
     assert_eq!(engine.eval::<Type>("Int64")?, Type::Int64);
     assert_eq!(engine.eval::<Type>("UInt32")?, Type::UInt32);
     assert_eq!(engine.eval::<Type>("UInt64")?, Type::UInt64);
+    assert_eq!(engine.eval::<Type>("Int8")?, Type::Int8);
+    assert_eq!(engine.eval::<Type>("Int16")?, Type::Int16);
+    assert_eq!(engine.eval::<Type>("Bool8")?, Type::Bool8);
+    assert_eq!(engine.eval::<Type>("Bool32")?, Type::Bool32);
+    assert_eq!(engine.eval::<Type>("Char8")?, Type::Char8);
+    assert_eq!(engine.eval::<Type>("Char16")?, Type::Char16);
+    assert_eq!(
+        engine.eval::<rhai::INT>(
+            "Int8.size + Int16.size + Bool8.size + Bool32.size + Char8.size + Char16.size"
+        )?,
+        11
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_native_bitfield() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Bits are laid out from the least significant bit, `_` skips bits.
+    assert_eq!(
+        engine.eval::<Type>(
+            r#"native Test { id: UInt16, flags: UInt32 { alive: 1, team: 3, _: 4, level: 8 }, hp: Int32 }; Test"#
+        )?,
+        {
+            let mut fields: BTreeMap<u32, Vec<Field>> = BTreeMap::new();
+            fields.insert(0, vec![Field::new("id".into(), Type::UInt16)]);
+            fields.insert(
+                2,
+                vec![Field::new(
+                    "flags".into(),
+                    Type::Bitfield(Bitfield {
+                        storage: Box::new(Type::UInt32),
+                        bits: vec![
+                            Bits {
+                                name: "alive".into(),
+                                offset: 0,
+                                width: 1,
+                            },
+                            Bits {
+                                name: "team".into(),
+                                offset: 1,
+                                width: 3,
+                            },
+                            Bits {
+                                name: "level".into(),
+                                offset: 8,
+                                width: 8,
+                            },
+                        ],
+                    }),
+                )],
+            );
+            fields.insert(6, vec![Field::new("hp".into(), Type::Int32)]);
+            Type::Struct(Struct::new(fields))
+        }
+    );
+
+    // The layout is visible from scripts.
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native Test { id: UInt8, flags: UInt8 { a: 2, _: 1, b: 5 } };
+            let f = Test.fields[1];
+            f.ty.bits.reduce(|acc, b| `${acc} ${b.name}@${b.offset}:${b.width}`, `${f.name}@${f.offset}:`)"#
+        )?,
+        "flags@1: a@0:2 b@3:5"
+    );
+
+    // Bits are extracted on decode and only the given bits are replaced on encode.
+    assert_eq!(
+        engine.eval::<rhai::INT>(
+            r#"native Test { flags: UInt16 { a: 4, b: 4, c: 8 } };
+            let v = decode(Test, encode(Test, #{ flags: #{ a: 3, b: 15, c: 200 } }));
+            v.flags.a * 10000 + v.flags.b * 1000 + v.flags.c"#
+        )?,
+        30000 + 15000 + 200
+    );
+
+    // Signed storage sign extends.
+    assert_eq!(
+        engine.eval::<rhai::INT>(
+            r#"native Test { flags: Int8 { a: 3, b: 5 } };
+            decode(Test, encode(Test, #{ flags: #{ a: -2, b: 7 } })).flags.a"#
+        )?,
+        -2
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval::<()>(r#"native Test { flags: UInt8 { a: 4, b: 5 } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { flags: Fp32 { a: 1 } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { flags: UInt8 { a: 1, a: 1 } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { flags: UInt8 { a: 0 } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { flags: UInt8 { a: x } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { flags: UInt8 { a: 1 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<rhai::Blob>(
+            r#"native Test { flags: UInt8 { a: 2 } }; encode(Test, #{ flags: #{ a: 4 } })"#
+        )
+        .is_err());
+    assert!(engine
+        .eval::<rhai::Blob>(
+            r#"native Test { flags: UInt8 { a: 2 } }; encode(Test, #{ flags: #{ z: 1 } })"#
+        )
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_native_enum() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Variants without a value follow the previous one.
+    assert_eq!(
+        engine.eval::<Type>(r#"native enum Team : UInt8 { Red = 1, Blue, Green = 7 }; Team"#)?,
+        Type::Enum(Enum {
+            name: "Team".into(),
+            repr: Box::new(Type::UInt8),
+            variants: vec![("Red".into(), 1), ("Blue".into(), 2), ("Green".into(), 7)],
+            flags: false,
+        })
+    );
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native flags Access : UInt32 { None = 0, Read, Write, Exec = 1 << 4 };
+            let v = Access.variants; `${Access.size} ${v.None} ${v.Read} ${v.Write} ${v.Exec}`"#
+        )?,
+        "4 0 1 2 16"
+    );
+
+    // Values show their name, convert to `INT` and compare against names and numbers.
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native enum Team : UInt8 { Red = 1, Blue = 2 };
+            let v = decode(Team, encode(Team, "Blue"));
+            `${v} ${v.name} ${v.to_int() + 1} ${v == "Blue"} ${v == 2} ${v != "Red"}`"#
+        )?,
+        "Blue Blue 3 true true true"
+    );
+
+    // Unknown discriminants round-trip.
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native enum Team : UInt8 { Red = 1, Blue = 2 };
+            let v = decode(Team, encode(Team, 200));
+            let w = decode(Team, encode(Team, v));
+            `${w} ${w.name == ()} ${w.value}`"#
+        )?,
+        "Team(200) true 200"
+    );
+
+    // Flags decode to the names of the set flags, unknown bits are kept as a number.
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native flags Access : UInt32 { None = 0, Read, Write, Exec = 1 << 4 };
+            let a = decode(Access, encode(Access, ["Read", "Exec", 0x100]));
+            let b = decode(Access, encode(Access, 0));
+            let c = decode(Access, encode(Access, "Write"));
+            `${a} ${b} ${c}`"#
+        )?,
+        r#"["Read", "Exec", 256] ["None"] ["Write"]"#
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval::<()>(r#"native enum Team : Fp32 { Red }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native enum Team : UInt8 { Red = 256 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native enum Team : UInt8 { Red, Red }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native enum Team : UInt8 { Red = "a" }"#)
+        .is_err());
+    assert!(engine.eval::<()>(r#"native enum Team : UInt8 {}"#).is_err());
+    assert!(engine.eval::<()>(r#"native enum Team { Red }"#).is_err());
+    assert!(engine
+        .eval::<()>(r#"native flags Access : Int64 { Read = -1, Write }"#)
+        .unwrap_err()
+        .to_string()
+        .contains("no next flag bit"));
+    assert!(engine
+        .eval::<rhai::Blob>(r#"native enum Team : UInt8 { Red }; encode(Team, "Blue")"#)
+        .is_err());
+    assert!(engine
+        .eval::<rhai::Blob>(r#"native enum Team : UInt8 { Red }; encode(Team, 300)"#)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_native_union() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Explicit offsets move the cursor, union members share an offset and the union is as large as its largest member.
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native Test { id: UInt16, union { i: Int32, f: Fp32, b: UInt8 }, @0x10 hp: Int16, tail: UInt8 };
+            let offsets = Test.fields.map(|f| `${f.name}@${f.offset}`);
+            `${offsets} ${Test.size}`"#
+        )?,
+        r#"["id@0", "i@2", "f@2", "b@2", "hp@16", "tail@18"] 19"#
+    );
+
+    // Every member of a union is decoded, writing one of them is enough.
+    assert_eq!(
+        engine.eval::<String>(
+            r#"native Test { union { raw: UInt32, parts: UInt32 { low: 16, high: 16 } }, hp: UInt8 };
+            let v = decode(Test, encode(Test, #{ raw: 0x12345678, hp: 7 }));
+            let w = decode(Test, encode(Test, #{ parts: #{ low: 1, high: 2 }, hp: 7 }));
+            `${v.parts.low} ${v.parts.high} ${w.raw} ${v.hp}`"#
+        )?,
+        "22136 4660 131073 7"
+    );
+
+    // Overlapping fields outside of a union are rejected unless explicitly allowed.
+    match engine.eval::<()>(r#"native Test { a: UInt32, @2 b: UInt16 }"#) {
+        Err(e) => assert!(e
+            .to_string()
+            .contains("field `b` at 0x2 overlaps field `a` at 0x0")),
+        Ok(_) => panic!("overlapping fields were accepted"),
+    }
+    assert_eq!(
+        engine
+            .eval::<rhai::INT>(r#"native(overlap) Test { a: UInt32, @2 b: UInt16 }; Test.size"#)?,
+        4
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval::<()>(r#"native Test { union { a: UInt32 }, b: UInt8, @0 c: UInt8 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native(packed) Test { a: UInt32 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { @-1 a: UInt32 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { union { ^ 4 } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { @0xFFFFFFFF a: UInt32 }"#)
+        .unwrap_err()
+        .to_string()
+        .contains("out of range"));
+    assert!(engine
+        .eval::<()>(r#"native Test { a: UInt8, ^0xFFFFFFFF b: UInt8 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native Test { @0xFFFFFFFE union { a: UInt32 } }"#)
+        .is_err());
+    assert!(engine
+        .eval::<rhai::Blob>(r#"native Test { union { a: UInt32, b: Fp32 } }; encode(Test, #{})"#)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_native_align() -> Result<(), Box<EvalAltResult>> {
+    use std::mem::{align_of, offset_of, size_of};
+
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Offsets, size and alignment of the native `Test`.
+    let layout = |script: &str| -> Result<String, Box<EvalAltResult>> {
+        engine.eval::<String>(&format!(
+            r#"{}; `${{Test.fields.map(|f| f.offset)}} ${{Test.size}} ${{Test.align}}`"#,
+            script
+        ))
+    };
+
+    #[repr(C)]
+    struct Mixed {
+        a: u8,
+        b: u64,
+        c: u16,
+        d: u32,
+        e: u8,
+    }
+    assert_eq!(
+        layout(r#"native(align) Test { a: UInt8, b: UInt64, c: UInt16, d: UInt32, e: UInt8 }"#)?,
+        format!(
+            "[{}, {}, {}, {}, {}] {} {}",
+            offset_of!(Mixed, a),
+            offset_of!(Mixed, b),
+            offset_of!(Mixed, c),
+            offset_of!(Mixed, d),
+            offset_of!(Mixed, e),
+            size_of::<Mixed>(),
+            align_of::<Mixed>()
+        )
+    );
+
+    // Nested structs keep their own alignment and tail padding, arrays align like their elements.
+    #[repr(C)]
+    struct Inner {
+        x: u16,
+        y: u8,
+    }
+    #[repr(C)]
+    struct Outer {
+        a: u8,
+        inner: Inner,
+        s: [u8; 3],
+        w: [u16; 2],
+        p: u64,
+    }
+    assert_eq!(
+        layout(
+            r#"native(align) Inner { x: UInt16, y: UInt8 };
+            native(align) Test { a: UInt8, inner: Inner, s: String(3), w: Collection(UInt16, 2), p: Pointer64(UInt8) }"#
+        )?,
+        format!(
+            "[{}, {}, {}, {}, {}] {} {}",
+            offset_of!(Outer, a),
+            offset_of!(Outer, inner),
+            offset_of!(Outer, s),
+            offset_of!(Outer, w),
+            offset_of!(Outer, p),
+            size_of::<Outer>(),
+            align_of::<Outer>()
+        )
+    );
+
+    // Unions are aligned for their strictest member and padded to it.
+    #[repr(C)]
+    union Value {
+        _a: u8,
+        _b: [u8; 5],
+        _c: u32,
+    }
+    #[repr(C)]
+    struct Tagged {
+        tag: u8,
+        value: Value,
+        tail: u16,
+    }
+    assert_eq!(
+        layout(
+            r#"native(align) Test { tag: UInt8, union { a: UInt8, b: String(5), c: UInt32 }, tail: UInt16 }"#
+        )?,
+        format!(
+            "[{0}, {1}, {1}, {1}, {2}] {3} {4}",
+            offset_of!(Tagged, tag),
+            offset_of!(Tagged, value),
+            offset_of!(Tagged, tail),
+            size_of::<Tagged>(),
+            align_of::<Tagged>()
+        )
+    );
+
+    // `pack = N` limits field alignment like `#pragma pack(N)`.
+    #[repr(C, packed(2))]
+    struct Packed {
+        a: u8,
+        b: u64,
+        c: u8,
+    }
+    assert_eq!(
+        layout(r#"native(pack = 2) Test { a: UInt8, b: UInt64, c: UInt8 }"#)?,
+        format!(
+            "[{}, {}, {}] {} {}",
+            offset_of!(Packed, a),
+            offset_of!(Packed, b),
+            offset_of!(Packed, c),
+            size_of::<Packed>(),
+            align_of::<Packed>()
+        )
+    );
+
+    // Structs stay packed by default, explicit offsets are used as is.
+    assert_eq!(
+        layout(r#"native Test { a: UInt8, b: UInt64 }"#)?,
+        "[0, 1] 9 1"
+    );
+    assert_eq!(
+        layout(r#"native(align) Test { a: UInt8, @3 b: UInt32, c: UInt8 }"#)?,
+        "[0, 3, 7] 8 4"
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval::<()>(r#"native(pack = 3) Test { a: UInt32 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native(pack = "a") Test { a: UInt32 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native(pack) Test { a: UInt32 }"#)
+        .is_err());
+    assert!(engine
+        .eval::<()>(r#"native(align) Test { @0xFFFFFFFE a: UInt8, b: UInt32 }"#)
+        .is_err());
 
     Ok(())
 }
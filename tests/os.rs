@@ -2,7 +2,7 @@ use std::cell::RefCell;
 
 use memflow::{
     dummy::*,
-    os::OsInner,
+    os::Os as OsInner,
     prelude::OsInstance,
     types::{size, Address},
 };
@@ -2,16 +2,48 @@ use std::cell::RefCell;
 
 use memflow::{
     dummy::*,
-    os::OsInner,
-    prelude::IntoProcessInstance,
+    os::Os as OsInner,
+    prelude::{ArchitectureIdent, IntoProcessInstance, MemoryView, ModuleInfo},
     types::{size, Address},
 };
 // Used for trait_obj
 use cglue::arc::CArc;
 use cglue::*;
 
-use rhai::{packages::Package, Engine, EvalAltResult, Scope};
-use rhai_memflow::{process::SharedProcess, MemflowPackage};
+use rhai::{packages::Package, Engine, EvalAltResult, ImmutableString, Scope};
+use rhai_memflow::{
+    path::{PointerPath, Term},
+    process::{glob_match, SharedProcess},
+    MemflowPackage,
+};
+
+type DummyProcess = <DummyOs as OsInner>::IntoProcessType;
+
+/// Dummy process with 60mb of memory, which tests can add modules to or write into before calling `setup`.
+fn dummy_process() -> DummyProcess {
+    let mem = DummyMemory::new(size::mb(64));
+    let mut os = DummyOs::new(mem);
+    let pid = os.alloc_process(size::mb(60), &[]);
+    os.into_process_by_pid(pid).unwrap()
+}
+
+/// Engine with our memflow package registered and a scope holding `prc` as `PROCESS`.
+fn setup(prc: DummyProcess) -> (Engine, Scope<'static>) {
+    let mut engine = Engine::new();
+
+    // Register our memflow package.
+    let package = MemflowPackage::new();
+    package.register_into_engine(&mut engine);
+
+    // Push process (and by extension the moved kernel) to the scope.
+    let mut scope = Scope::new();
+    let ref_to_count: CArc<cglue::trait_group::c_void> = CArc::default();
+    let shared_process: SharedProcess =
+        RefCell::new(group_obj!((prc, ref_to_count) as IntoProcessInstance));
+    scope.push_constant("PROCESS", shared_process);
+
+    (engine, scope)
+}
 
 #[test]
 fn test_process() -> Result<(), Box<EvalAltResult>> {
@@ -45,3 +77,1577 @@ fn test_process() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[test]
+fn test_process_write() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    // Write works
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"PROCESS.write(Int32, BASE, -42); PROCESS.read(Int32, BASE)"#
+        )?,
+        -42
+    );
+
+    // Write through pointer works
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"let p = ptr(UInt16, BASE + 8); PROCESS.write(p, 1337); PROCESS.read(p)"#
+        )?,
+        1337
+    );
+
+    // Raw bytes work
+    assert_eq!(
+        engine.eval_with_scope::<rhai::Blob>(
+            &mut scope,
+            r#"PROCESS.write_bytes(BASE + 4, encode(UInt16, 0x0102)); PROCESS.read_bytes(BASE + 4, 2)"#
+        )?,
+        0x0102_u16.to_ne_bytes().to_vec()
+    );
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.read_bytes(BASE, -1)"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.read_bytes(BASE, 0x7FFFFFFFFFFF)"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.write(String(0xFFFFFFFF), BASE, "")"#)
+        .is_err());
+
+    // Indexing pointers works
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"
+            let p = ptr(UInt16, BASE + 8);
+            PROCESS.write(p + 1, 7);
+            PROCESS.read(p, 1) + PROCESS.read(p.offset(1))
+            "#
+        )?,
+        14
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"let p = PROCESS.view(ptr(UInt16, BASE + 8)); p[1] = 9; p[1]"#
+        )?,
+        9
+    );
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.view(ptr(UInt64, addr(8)))[-2]"#)
+        .is_err());
+
+    // Write native works
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(
+            &mut scope,
+            r#"
+            native Test { num: Int32, name: String(8), nums: Collection(UInt8, 2) };
+            PROCESS.write(Test, BASE + 16, #{ num: 7, name: "abc", nums: [1, 2] });
+            let test = PROCESS.read(Test, BASE + 16);
+            `${test.num}${test.name}${test.nums[1]}`
+            "#
+        )?,
+        "7abc2"
+    );
+
+    // Bitfields only replace the written bits of their storage unit
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"
+            native Flags { flags: UInt16 { alive: 1, team: 3, _: 4, level: 8 } };
+            PROCESS.write(UInt16, BASE + 32, 0xffff);
+            PROCESS.write(Flags, BASE + 32, #{ flags: #{ alive: 0, team: 2 } });
+            PROCESS.read(UInt16, BASE + 32)
+            "#
+        )?,
+        0xfff4
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"let flags = PROCESS.read(Flags, BASE + 32).flags; flags.team * 1000 + flags.level"#
+        )?,
+        2255
+    );
+
+    // Enums read back as named values and can be written by name or number
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"
+            native enum Team : UInt16 { Red = 1, Blue = 2 };
+            native Player { team: Team, hp: Int32 };
+            PROCESS.write(Player, BASE + 40, #{ team: "Blue", hp: 10 });
+            let before = PROCESS.read(Player, BASE + 40).team;
+            PROCESS.write(Team, BASE + 40, 9);
+            `${before} ${PROCESS.read(Team, BASE + 40)} ${PROCESS.read(UInt16, BASE + 40)}`
+            "#
+        )?,
+        "Blue Team(9) 9"
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.write(Int32, BASE, "str")"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.write(String(2), BASE, "long")"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.write(WideString(2), BASE, "long")"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"native Test { num: Int32, num2: Int32 }; PROCESS.write(Test, BASE, #{ num: 1 })"#
+        )
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"PROCESS.write(Collection(UInt8, 2), BASE, [1, 2, 3])"#
+        )
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"PROCESS.write(Collection(UInt8, 2), BASE, 1)"#
+        )
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_view() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        native Vec2 { x: Fp32, y: Fp32 };
+        native Player { health: Int32, pos: Vec2, ids: Collection(UInt16, 4), next: Pointer64(Vec2) };
+        PROCESS.write(Player, BASE, #{ health: 100, pos: #{ x: 1.0, y: 2.0 }, ids: [1, 2, 3, 4], next: ptr(Vec2, BASE + 32) });
+        let player = PROCESS.view(Player, BASE);
+        "#,
+    )?;
+
+    // Reading fields works
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"player.health + player.ids[3]"#)?,
+        104
+    );
+
+    // Nested and pointer fields are views
+    assert_eq!(
+        engine.eval_with_scope::<rhai::FLOAT>(&mut scope, r#"player.pos.y + player.next.x"#)?,
+        2.0
+    );
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(&mut scope, r#"type_of(player.pos)"#)?,
+        "View"
+    );
+
+    // Writing fields works
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"player.health = 50; player.ids[0] = 9; player.health + player.ids[0]"#
+        )?,
+        59
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::FLOAT>(
+            &mut scope,
+            r#"player.pos.x = 4.5; player.next.y = 0.5; player.read().pos.x + player.next.y"#
+        )?,
+        5.0
+    );
+
+    // Pointer fields can be pointed at other views
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"player.next = player.pos; PROCESS.view(Player, BASE).next.addr()"#
+        )?,
+        base_addr + 4
+    );
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"player.missing"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"player.ids[4]"#)
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"PROCESS.view(Collection(Collection(UInt64, 0x1000000), 0x100), BASE)[0x40][0]"#
+        )
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"player.health = "str""#)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_dyn_collection() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        native Item { id: UInt16, value: Int16 };
+        native List { count: UInt32, ^ 4, items: Pointer64(Collection(Item, "count")) };
+        native Inline { kind: UInt8, len: UInt8, data: Collection(UInt8, "len") };
+        PROCESS.write(Collection(Item, 3), BASE + 0x100, [#{ id: 1, value: -1 }, #{ id: 2, value: -2 }, #{ id: 3, value: -3 }]);
+        PROCESS.write(List, BASE, #{ count: 3, items: BASE + 0x100 });
+        PROCESS.write(Inline, BASE + 0x200, #{ kind: 7, len: 4, data: [1, 2, 3, 4] });
+        "#,
+    )?;
+
+    // Pointers resolve to a collection of the length read from their sibling field.
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"let items = PROCESS.read(PROCESS.read(List, BASE).items); `${items.len()} ${items[2].value}`"#
+        )?,
+        "3 -3"
+    );
+
+    // Trailing collections are not part of the struct's size and are read after it.
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"let v = PROCESS.read(Inline, BASE + 0x200); `${Inline.size} ${v.kind} ${v.data}`"#
+        )?,
+        "2 7 [1, 2, 3, 4]"
+    );
+
+    // Views, batches and blobs resolve the length the same way.
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"let view = PROCESS.view(Inline, BASE + 0x200); view.data[3] + view.kind"#
+        )?,
+        11
+    );
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"let values = PROCESS.read_many([[Inline, BASE + 0x200], [List, BASE]]);
+            `${values[0].data.len()} ${PROCESS.read(values[1].items).len()}`"#
+        )?,
+        "4 3"
+    );
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"let b = blob(5, 0); b[1] = 2; b[2] = 5; b[3] = 6; `${decode(Inline, b).data}`"#
+        )?,
+        "[5, 6]"
+    );
+
+    // Lengths beyond the safety limit are refused instead of read.
+    MemflowPackage::set_max_collection_len(3);
+    let limited = engine.eval_with_scope::<()>(&mut scope, r#"PROCESS.read(Inline, BASE + 0x200)"#);
+    let max = engine.eval_with_scope::<rhai::INT>(&mut scope, r#"max_collection_len()"#);
+    MemflowPackage::set_max_collection_len(0x10000);
+    assert!(limited.is_err());
+    assert_eq!(max?, 3);
+    // Scripts cannot raise the limit.
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"set_max_collection_len(0x100000)"#)
+        .is_err());
+    assert_eq!(MemflowPackage::max_collection_len(), 0x10000);
+
+    // Make sure we don't panic and instead throw errors.
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"native Bad { data: Collection(UInt8, "len") }"#
+        )
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"PROCESS.read(Pointer64(Collection(UInt8, "len")), BASE)"#
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("is given by the field `len`"));
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(
+            &mut scope,
+            r#"type_of(PROCESS.read_many([[Pointer32(Collection(UInt8, "len")), BASE]])[0])"#
+        )?,
+        "ReadError"
+    );
+    // Element offsets past 4gb do not wrap around, the empty elements write nothing.
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"native Gap { a: Collection(UInt8, 0), @0x80000000 b: Collection(UInt8, 0) };
+        let gap = #{ a: [], b: [] };
+        PROCESS.write(Collection(Gap, 3), BASE, [gap, gap, gap])"#,
+    )?;
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"native Bad { len: Fp32, data: Collection(UInt8, "len") }"#
+        )
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"native Bad { len: UInt8, data: Collection(UInt8, "len"), after: UInt8 }"#
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("must be the last field"));
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"native Bad { inline: Inline, after: UInt8 }"#)
+        .is_err());
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"native Good { len: UInt8, data: Pointer64(Collection(UInt8, "len")), after: UInt8 }"#,
+    )?;
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"PROCESS.read(Collection(UInt8, "len"), BASE)"#
+        )
+        .is_err());
+    assert!(engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"let view = PROCESS.view(Inline, BASE + 0x200); view.data[4]"#
+        )
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_read_many() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        PROCESS.write(Int32, BASE, 1);
+        PROCESS.write(Fp32, BASE + 0x2000, 2.5);
+        let values = PROCESS.read_many([[Int32, BASE], ptr(Fp32, BASE + 0x2000), [Int32, addr(0x10)]]);
+        "#,
+    )?;
+
+    // Every entry is read
+    assert_eq!(
+        engine.eval_with_scope::<rhai::FLOAT>(&mut scope, r#"values[0] + values[1]"#)?,
+        3.5
+    );
+
+    // Failed entries are errors without affecting the rest of the batch
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(&mut scope, r#"type_of(values[2])"#)?,
+        "ReadError"
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"values[2].addr"#)?,
+        Address::from(0x10)
+    );
+
+    // Malformed requests are errors
+    assert!(engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.read_many([[Int32]])"#)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_resolve() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    // [[BASE] + 0x18] + 0x40
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        PROCESS.write(Address64, BASE, BASE + 0x100);
+        PROCESS.write(Address64, BASE + 0x118, BASE + 0x200);
+        PROCESS.write(Int32, BASE + 0x240, 1234);
+        "#,
+    )?;
+
+    // Resolving works
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"PROCESS.resolve(BASE, [0x18, 0x40])"#)?,
+        base_addr + 0x240
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"PROCESS.read_chain(Int32, BASE, [0x18, 0x40])"#
+        )?,
+        1234
+    );
+
+    // Errors name the failing level
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.resolve(BASE, [0x18, 0x48, 0x8])"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("level 3: null pointer"));
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.resolve(addr(0x10), [0x18])"#)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("level 1: failed to read pointer"),
+        "{}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_process_eval_addr() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let mut prc = dummy_process();
+    prc.proc.add_modules(1, size::kb(1));
+    let module_addr = prc.proc.modules[0].base;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", module_addr);
+
+    // [[dummy.so + 0x40] + 0x10] + 0x8
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        PROCESS.write(Address64, BASE + 0x40, BASE + 0x100);
+        PROCESS.write(Address64, BASE + 0x110, BASE + 0x200);
+        "#,
+    )?;
+
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.eval_addr("dummy.so+0x40 -> 0x10 -> +0x8")"#
+        )?,
+        module_addr + 0x208
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.eval_addr("`dummy.so` + 0x50 - 16 -> 0x20 - 0x10 -> -0x8")"#
+        )?,
+        module_addr + 0x1f8
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"PROCESS.eval_addr("dummy.so")"#)?,
+        module_addr
+    );
+
+    // Syntax errors report their column
+    let err = engine
+        .eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.eval_addr("dummy.so+0x40 -> * 0x10")"#,
+        )
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("column 18: unexpected character `*`"));
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.eval_addr("dummy.so+0x40 ->")"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("column 17: expected a number"));
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.eval_addr("dummy.so -> other.so")"#)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("column 13: module `other.so` is only allowed before the first `->`"));
+
+    // Resolution errors
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.eval_addr("missing.so+0x40")"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("module `missing.so`"));
+
+    Ok(())
+}
+
+#[test]
+fn test_pointer_path_parse() {
+    let path: PointerPath = "Game.dll+0x1234 -> 0x10 -> -8".parse().unwrap();
+    assert_eq!(
+        path,
+        PointerPath {
+            base: vec![Term::Module("Game.dll".into()), Term::Offset(0x1234)],
+            offsets: vec![0x10, -8],
+        }
+    );
+    assert_eq!(path.to_string(), "Game.dll+0x1234 -> 0x10 -> -0x8");
+
+    let err = PointerPath::parse("Game.dll+0x1234 -> 0x10 0x20").unwrap_err();
+    assert_eq!(err.column, 25);
+    let err = PointerPath::parse("-Game.dll").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "column 2: cannot subtract module `Game.dll`"
+    );
+    let err = PointerPath::parse("\"Game.dll+0x10").unwrap_err();
+    assert_eq!(err.to_string(), "column 1: unterminated module name");
+}
+
+#[test]
+fn test_process_find_pattern() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let mut prc = dummy_process();
+    prc.proc.add_modules(1, size::kb(1));
+    let base_addr = prc.proc.info.address;
+    let module_addr = prc.proc.modules[0].base;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+    scope.push_constant("MODULE", module_addr);
+    scope.push_constant(
+        "PATTERN",
+        ImmutableString::from("48 8B 05 ?? ?? ?? ?? 48 85 C0"),
+    );
+
+    // One match inside the module, one crossing a page and one crossing a scan chunk of the range below.
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        let code = blob();
+        for b in [0x48, 0x8B, 0x05, 0x11, 0x22, 0x33, 0x44, 0x48, 0x85, 0xC0] {
+            code.push(b);
+        }
+        PROCESS.write_bytes(MODULE + 0x10, code);
+        PROCESS.write_bytes(BASE + 0x1ffc, code);
+        PROCESS.write_bytes(BASE + 0xfcffd, code);
+        "#,
+    )?;
+
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.find_pattern(PATTERN, PROCESS.mod("dummy.so"))"#
+        )?,
+        module_addr + 0x10
+    );
+
+    // Ranges starting in unmapped memory skip the unreadable pages.
+    assert!(engine
+        .eval_with_scope::<rhai::Blob>(&mut scope, r#"PROCESS.read_bytes(BASE - 0x3000, 4)"#)
+        .is_err());
+    let found = engine.eval_with_scope::<rhai::Array>(
+        &mut scope,
+        r#"PROCESS.find_pattern_all(PATTERN, BASE - 0x3000, 0x110000)"#,
+    )?;
+    let mut found: Vec<Address> = found.into_iter().map(|a| a.cast::<Address>()).collect();
+    found.retain(|a| *a != module_addr + 0x10);
+    assert_eq!(found, vec![base_addr + 0x1ffc, base_addr + 0xfcffd]);
+
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.find_pattern("48 85 C0", BASE + 0x1ffd, 0x1000)"#
+        )?,
+        base_addr + 0x2003
+    );
+    assert!(engine.eval_with_scope::<bool>(
+        &mut scope,
+        r#"PROCESS.find_pattern("DE AD BE EF", BASE, 0x10000) == ()"#
+    )?);
+
+    // Invalid patterns
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.find_pattern("48 8G", BASE, 0x10)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid pattern byte 2 `8G`"));
+
+    Ok(())
+}
+
+#[test]
+fn test_process_resolve_rel32() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    // mov rax, [rip+0x1000]; call -0x100; jmp +0x20
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        PROCESS.write(Collection(UInt8, 3), BASE + 0x100, [0x48, 0x8B, 0x05]);
+        PROCESS.write(Int32, BASE + 0x103, 0x1000);
+        PROCESS.write(UInt8, BASE + 0x200, 0xE8);
+        PROCESS.write(Int32, BASE + 0x201, -256);
+        PROCESS.write(UInt8, BASE + 0x300, 0xE9);
+        PROCESS.write(Int32, BASE + 0x301, 0x20);
+        "#,
+    )?;
+
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"
+            let insn = PROCESS.find_pattern("48 8B 05 ?? ?? ?? ??", BASE, 0x1000);
+            PROCESS.resolve_rel32(insn, 3, 7)
+            "#
+        )?,
+        base_addr + 0x1107
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"PROCESS.resolve_call(BASE + 0x200)"#)?,
+        base_addr + 0x105
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"PROCESS.resolve_jmp(BASE + 0x300)"#)?,
+        base_addr + 0x325
+    );
+
+    // The opcode is checked
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.resolve_jmp(BASE + 0x200)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("expected `jmp rel32` (E9) at"));
+
+    Ok(())
+}
+
+#[test]
+fn test_process_scanner() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let prc = dummy_process();
+    let base_addr = prc.proc.info.address;
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("BASE", base_addr);
+
+    // Unaligned values are not candidates and unreadable regions are skipped.
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"
+        PROCESS.write(Int32, BASE + 0x100, 100);
+        PROCESS.write(Int32, BASE + 0x2004, 100);
+        PROCESS.write(Int32, BASE + 0x3001, 100);
+        PROCESS.write(Fp32, BASE + 0x3100, 1.5);
+        let scanner = PROCESS.scanner(Int32, [[BASE, 0x4000], [BASE - 0x2000, 0x1000]]);
+        scanner.first_scan(100);
+        "#,
+    )?;
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"scanner.results()"#)?
+            .into_iter()
+            .map(|a| a.cast::<Address>())
+            .collect::<Vec<_>>(),
+        vec![base_addr + 0x100, base_addr + 0x2004]
+    );
+
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(
+                &mut scope,
+                r#"
+            PROCESS.write(Int32, BASE + 0x100, 101);
+            scanner.next_scan("increased");
+            scanner.results()
+            "#
+            )?
+            .into_iter()
+            .map(|a| a.cast::<Address>())
+            .collect::<Vec<_>>(),
+        vec![base_addr + 0x100]
+    );
+
+    // Unknown initial values
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"
+            let unknown = PROCESS.scanner(Int32, [[BASE + 0x8000, 0x2000]]);
+            unknown.first_scan_unknown();
+            unknown.count
+            "#
+        )?,
+        0x800
+    );
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(
+                &mut scope,
+                r#"
+            PROCESS.write(Int32, BASE + 0x8010, 5);
+            unknown.next_scan("changed");
+            unknown.next_scan("unchanged");
+            unknown.next_scan("between", 1, 10);
+            unknown.results(10)
+            "#
+            )?
+            .into_iter()
+            .map(|a| a.cast::<Address>())
+            .collect::<Vec<_>>(),
+        vec![base_addr + 0x8010]
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"
+            PROCESS.write(Int32, BASE + 0x8010, 4);
+            unknown.next_scan("exact", 6);
+            unknown.count
+            "#
+        )?,
+        0
+    );
+
+    // Dense candidates keep their snapshot until few enough are left.
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"
+            PROCESS.write_bytes(BASE + 0xa000, blob(0x10, 7));
+            let bytes = PROCESS.scanner(UInt8, [[BASE + 0xa000, 0x2000]]);
+            bytes.first_scan_unknown();
+            bytes.next_scan("unchanged");
+            let dense = bytes.count;
+            PROCESS.write(UInt8, BASE + 0xa008, 9);
+            PROCESS.write(UInt8, BASE + 0xb000, 1);
+            bytes.next_scan("changed");
+            let changed = bytes.results() == [BASE + 0xa008, BASE + 0xb000];
+            bytes.next_scan("decreased");
+            `${dense} ${changed} ${bytes.count}`
+            "#
+        )?,
+        "8192 true 0"
+    );
+
+    // After an exact scan for a common value dense candidates only keep that value.
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"
+            PROCESS.write_bytes(BASE + 0xc000, blob(0x2000, 3));
+            let common = PROCESS.scanner(UInt8, [[BASE + 0xc000, 0x2000]]);
+            common.first_scan(3);
+            let hits = common.count;
+            PROCESS.write(UInt8, BASE + 0xc010, 4);
+            common.next_scan("increased");
+            `${hits} ${common.results() == [BASE + 0xc010]}`
+            "#
+        )?,
+        "8192 true"
+    );
+
+    // Floating point values
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(
+                &mut scope,
+                r#"
+            let floats = PROCESS.scanner(Fp32, [[BASE, 0x4000]]);
+            floats.first_scan(1.5);
+            floats.results()
+            "#
+            )?
+            .into_iter()
+            .map(|a| a.cast::<Address>())
+            .collect::<Vec<_>>(),
+        vec![base_addr + 0x3100]
+    );
+
+    // Errors
+    let err = engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"PROCESS.scanner(Int32, [[BASE, 0x10]]).next_scan("changed")"#,
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("requires a first scan"));
+    let err = engine
+        .eval_with_scope::<()>(&mut scope, r#"scanner.next_scan("bigger")"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown scan `bigger`"));
+    let err = engine
+        .eval_with_scope::<()>(&mut scope, r#"scanner.next_scan("exact")"#)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("takes 1 value(s) but 0 were given"));
+    let err = engine
+        .eval_with_scope::<()>(&mut scope, r#"PROCESS.scanner(String(4), [])"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("cannot scan for `String` values"));
+    let err = engine
+        .eval_with_scope::<()>(
+            &mut scope,
+            r#"native flags Access : UInt32 { Read, Write }; PROCESS.scanner(Access, [])"#,
+        )
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("cannot scan for `Access` flags values"));
+
+    Ok(())
+}
+
+#[test]
+fn test_process_memory_map() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test, its memory map is made up of its modules.
+    let mut prc = dummy_process();
+    prc.proc.add_modules(1, size::kb(1));
+    let module = prc.proc.modules[0].clone();
+
+    let (engine, mut scope) = setup(prc);
+
+    engine.eval_with_scope::<()>(&mut scope, r#"let map = PROCESS.memory_map();"#)?;
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"map.len()"#)?,
+        1
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"map[0].base"#)?,
+        module.base
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"map[0].end"#)?,
+        module.base + module.size
+    );
+    assert!(engine.eval_with_scope::<bool>(
+        &mut scope,
+        r#"map[0].unknown && map[0].executable() && !map[0].writable()"#
+    )?);
+    assert!(engine.eval_with_scope::<bool>(
+        &mut scope,
+        r#"map[0].contains(map[0].base) && !map[0].contains(map[0].end)"#
+    )?);
+
+    // Filters
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"map.executable().in_module(PROCESS.mod("dummy.so")).len()"#
+        )?,
+        1
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"map.writable().len()"#)?,
+        0
+    );
+    let err = engine
+        .eval_with_scope::<rhai::Array>(&mut scope, r#"[1].writable()"#)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("cannot filter `i64` as a memory region"));
+
+    // Regions can be scanned directly
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"
+            PROCESS.write(UInt32, (map[0].base + 0x10).align_up(4), 0xdeadbeef);
+            let scanner = PROCESS.scanner(UInt32, map);
+            scanner.first_scan(0xdeadbeef);
+            scanner.results()[0]
+            "#
+        )?,
+        Address::from((module.base.to_umem() + 0x10 + 3) & !3)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_process_modules() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process to test.
+    let mut prc = dummy_process();
+    prc.proc.add_modules(3, size::kb(1));
+    let module = prc.proc.modules[0].clone();
+
+    let (engine, mut scope) = setup(prc);
+
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"PROCESS.modules().len()"#)?,
+        3
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"PROCESS.modules("*.SO").len()"#)?,
+        3
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"PROCESS.modules("*.dll").len()"#)?,
+        0
+    );
+
+    // Glob lookup
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"PROCESS.mod_like("*DUMMY*").base"#)?,
+        module.base
+    );
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(
+            &mut scope,
+            r#"PROCESS.mod_like("d?mmy.s?").name"#
+        )?,
+        "dummy.so"
+    );
+    let err = engine
+        .eval_with_scope::<Address>(&mut scope, r#"PROCESS.mod_like("d3d*").base"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("no module matching `d3d*`"));
+
+    // Remaining fields
+    engine.eval_with_scope::<()>(&mut scope, r#"let m = PROCESS.mod_like("*");"#)?;
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(&mut scope, r#"m.arch"#)?,
+        "x86_64"
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"m.parent_process"#)?,
+        module.parent_process
+    );
+    assert!(engine.eval_with_scope::<bool>(
+        &mut scope,
+        r#"m.contains(m.base) && m.contains(m.base + m.size - 1) && !m.contains(m.base + m.size)"#
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("*d3d*", "D3D11.dll"));
+    assert!(glob_match("a*b*c", "aXXbYc"));
+    assert!(glob_match("a?c", "abc"));
+    assert!(glob_match("*", ""));
+    assert!(!glob_match("a*b*c", "aXbY"));
+    assert!(!glob_match("a?c", "ac"));
+}
+
+/// Minimal PE32+ image exporting `CreateFileW` and `ReadFile` and importing `Sleep` from `kernel32.dll`, laid out
+/// as it would be once mapped.
+fn synthetic_pe(base: Address) -> Vec<u8> {
+    fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+    fn put16(image: &mut [u8], offset: usize, val: u16) {
+        put(image, offset, &val.to_le_bytes());
+    }
+    fn put32(image: &mut [u8], offset: usize, val: u32) {
+        put(image, offset, &val.to_le_bytes());
+    }
+    fn put64(image: &mut [u8], offset: usize, val: u64) {
+        put(image, offset, &val.to_le_bytes());
+    }
+
+    let mut image = vec![0u8; 0x3000];
+
+    // DOS header
+    put(&mut image, 0, b"MZ");
+    put32(&mut image, 0x3c, 0x80);
+
+    // NT headers
+    put(&mut image, 0x80, b"PE\0\0");
+    put16(&mut image, 0x84, 0x8664); // Machine
+    put16(&mut image, 0x86, 2); // NumberOfSections
+    put16(&mut image, 0x94, 0xf0); // SizeOfOptionalHeader
+    put16(&mut image, 0x96, 0x2022); // Characteristics
+
+    // Optional header
+    let opt = 0x98;
+    put16(&mut image, opt, 0x20b); // Magic
+    put32(&mut image, opt + 0x10, 0x1000); // AddressOfEntryPoint
+    put32(&mut image, opt + 0x14, 0x1000); // BaseOfCode
+    put64(&mut image, opt + 0x18, 0x180000000); // ImageBase
+    put32(&mut image, opt + 0x20, 0x1000); // SectionAlignment
+    put32(&mut image, opt + 0x24, 0x200); // FileAlignment
+    put16(&mut image, opt + 0x28, 6); // MajorOperatingSystemVersion
+    put16(&mut image, opt + 0x30, 6); // MajorSubsystemVersion
+    put32(&mut image, opt + 0x38, 0x3000); // SizeOfImage
+    put32(&mut image, opt + 0x3c, 0x400); // SizeOfHeaders
+    put16(&mut image, opt + 0x44, 2); // Subsystem
+    put32(&mut image, opt + 0x6c, 16); // NumberOfRvaAndSizes
+    put32(&mut image, opt + 0x70, 0x2000); // Export directory
+    put32(&mut image, opt + 0x74, 0x100);
+    put32(&mut image, opt + 0x78, 0x2100); // Import directory
+    put32(&mut image, opt + 0x7c, 0x28);
+    put32(&mut image, opt + 0x98, 0x2200); // Base relocation directory
+    put32(&mut image, opt + 0x9c, 0x10);
+    put32(&mut image, opt + 0xa0, 0x2500); // Debug directory
+    put32(&mut image, opt + 0xa4, 0x1c);
+    put32(&mut image, opt + 0xb8, 0x2300); // TLS directory
+    put32(&mut image, opt + 0xbc, 0x28);
+    put32(&mut image, opt + 0xd0, 0x2160); // Import address table
+    put32(&mut image, opt + 0xd4, 0x18);
+
+    // Section headers
+    let sections = opt + 0xf0;
+    put(&mut image, sections, b".text\0\0\0");
+    put32(&mut image, sections + 0x8, 0x1000); // VirtualSize
+    put32(&mut image, sections + 0xc, 0x1000); // VirtualAddress
+    put32(&mut image, sections + 0x10, 0x1000); // SizeOfRawData
+    put32(&mut image, sections + 0x14, 0x1000); // PointerToRawData
+    put32(&mut image, sections + 0x24, 0x60000020); // Characteristics
+    put(&mut image, sections + 0x28, b".rdata\0\0");
+    put32(&mut image, sections + 0x30, 0x1000);
+    put32(&mut image, sections + 0x34, 0x2000);
+    put32(&mut image, sections + 0x38, 0x1000);
+    put32(&mut image, sections + 0x3c, 0x2000);
+    put32(&mut image, sections + 0x4c, 0x40000040);
+
+    // Export directory
+    put32(&mut image, 0x200c, 0x2080); // Name
+    put32(&mut image, 0x2010, 1); // Base
+    put32(&mut image, 0x2014, 2); // NumberOfFunctions
+    put32(&mut image, 0x2018, 2); // NumberOfNames
+    put32(&mut image, 0x201c, 0x2040); // AddressOfFunctions
+    put32(&mut image, 0x2020, 0x2050); // AddressOfNames
+    put32(&mut image, 0x2024, 0x2060); // AddressOfNameOrdinals
+    put32(&mut image, 0x2040, 0x1100);
+    put32(&mut image, 0x2044, 0x1200);
+    put32(&mut image, 0x2050, 0x2090);
+    put32(&mut image, 0x2054, 0x20a0);
+    put16(&mut image, 0x2060, 0);
+    put16(&mut image, 0x2062, 1);
+    put(&mut image, 0x2080, b"test.dll\0");
+    put(&mut image, 0x2090, b"CreateFileW\0");
+    put(&mut image, 0x20a0, b"ReadFile\0");
+
+    // Import directory
+    put32(&mut image, 0x2100, 0x2140); // OriginalFirstThunk
+    put32(&mut image, 0x210c, 0x2180); // Name
+    put32(&mut image, 0x2110, 0x2160); // FirstThunk
+    put64(&mut image, 0x2140, 0x21a0);
+    put64(&mut image, 0x2148, 0x8000000000000010); // Ordinal 16
+    put64(&mut image, 0x2160, 0x21a0);
+    put64(&mut image, 0x2168, 0x8000000000000010);
+    put(&mut image, 0x2180, b"kernel32.dll\0");
+    put16(&mut image, 0x21a0, 7); // Hint
+    put(&mut image, 0x21a2, b"Sleep\0");
+
+    // Base relocations, a single block for the first page of `.text`
+    put32(&mut image, 0x2200, 0x1000); // VirtualAddress
+    put32(&mut image, 0x2204, 0x10); // SizeOfBlock
+    put16(&mut image, 0x2208, 0xa010);
+    put16(&mut image, 0x220a, 0xa020);
+    put16(&mut image, 0x220c, 0x3030);
+    put16(&mut image, 0x220e, 0); // Padding
+
+    // TLS directory, holding virtual addresses of the relocated image
+    let va = |rva: u64| base.to_umem() + rva;
+    put64(&mut image, 0x2300, va(0x2400)); // StartAddressOfRawData
+    put64(&mut image, 0x2308, va(0x2420)); // EndAddressOfRawData
+    put64(&mut image, 0x2310, va(0x2428)); // AddressOfIndex
+    put64(&mut image, 0x2318, va(0x2430)); // AddressOfCallBacks
+    put64(&mut image, 0x2430, va(0x1300));
+
+    // Debug directory with a CodeView entry
+    put32(&mut image, 0x2504, 0x12345678); // TimeDateStamp
+    put32(&mut image, 0x250c, 2); // Type
+    put32(&mut image, 0x2510, 0x30); // SizeOfData
+    put32(&mut image, 0x2514, 0x2600); // AddressOfRawData
+    put32(&mut image, 0x2518, 0x2600); // PointerToRawData
+    put(&mut image, 0x2600, b"RSDS");
+    put(&mut image, 0x2604, &(0..16).collect::<Vec<u8>>());
+    put32(&mut image, 0x2614, 3); // Age
+    put(&mut image, 0x2618, b"test.pdb\0");
+
+    image
+}
+
+#[test]
+fn test_process_module_symbols() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process with a module mapping the synthetic image.
+    let mut prc = dummy_process();
+    let module_addr = prc.proc.info.address + 0x10000;
+    prc.proc.modules.push(ModuleInfo {
+        address: Address::NULL,
+        parent_process: prc.proc.info.address,
+        base: module_addr,
+        size: 0x3000,
+        name: "test.dll".into(),
+        path: "C:\\test.dll".into(),
+        arch: ArchitectureIdent::X86(64, false),
+    });
+    prc.write_raw(module_addr, &synthetic_pe(module_addr))
+        .unwrap();
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("MODULE", module_addr);
+
+    engine.eval_with_scope::<()>(&mut scope, r#"let m = PROCESS.mod("test.dll");"#)?;
+
+    // Exports
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"m.exports(PROCESS).map(|e| e.name)"#)?
+            .into_iter()
+            .map(|n| n.cast::<ImmutableString>().to_string())
+            .collect::<Vec<_>>(),
+        vec!["CreateFileW", "ReadFile"]
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"m.exports(PROCESS).filter(|e| e.name == "ReadFile")[0].addr"#
+        )?,
+        module_addr + 0x1200
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.export_addr("test.dll", "CreateFileW")"#
+        )?,
+        module_addr + 0x1100
+    );
+    let err = engine
+        .eval_with_scope::<Address>(
+            &mut scope,
+            r#"PROCESS.export_addr("test.dll", "WriteFile")"#,
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("export `WriteFile` of `test.dll`"));
+
+    // Imports
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(&mut scope, r#"m.imports(PROCESS)[0].name"#)?,
+        "Sleep"
+    );
+
+    // Sections
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"m.sections(PROCESS).map(|s| s.name)"#)?
+            .into_iter()
+            .map(|n| n.cast::<ImmutableString>().to_string())
+            .collect::<Vec<_>>(),
+        vec![".text", ".rdata"]
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"m.sections(PROCESS)[1].base"#)?,
+        module_addr + 0x2000
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_process_pe() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process with a module mapping the synthetic image.
+    let mut prc = dummy_process();
+    let module_addr = prc.proc.info.address + 0x10000;
+    prc.proc.modules.push(ModuleInfo {
+        address: Address::NULL,
+        parent_process: prc.proc.info.address,
+        base: module_addr,
+        size: 0x3000,
+        name: "test.dll".into(),
+        path: "C:\\test.dll".into(),
+        arch: ArchitectureIdent::X86(64, false),
+    });
+    prc.write_raw(module_addr, &synthetic_pe(module_addr))
+        .unwrap();
+    // A copy whose export directory claims to reach past the end of the address space
+    let mut corrupt = synthetic_pe(module_addr + 0x4000);
+    corrupt[0x10c..0x110].copy_from_slice(&0xfffffff0u32.to_le_bytes());
+    prc.write_raw(module_addr + 0x4000, &corrupt).unwrap();
+    // A copy whose relocation directory is far too large to read
+    let mut corrupt = synthetic_pe(module_addr + 0x8000);
+    corrupt[0x134..0x138].copy_from_slice(&0xfffffff0u32.to_le_bytes());
+    prc.write_raw(module_addr + 0x8000, &corrupt).unwrap();
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("MODULE", module_addr);
+
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"let pe = PROCESS.pe(PROCESS.mod("test.dll"));"#,
+    )?;
+
+    // Headers
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"pe.headers.machine"#)?,
+        0x8664
+    );
+    assert!(engine.eval_with_scope::<bool>(&mut scope, r#"pe.headers.is_64"#)?);
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"pe.headers.entry_point"#)?,
+        module_addr + 0x1000
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"pe.headers.image_base"#)?,
+        Address::from(0x180000000u64)
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(
+            &mut scope,
+            r#"pe.data_directories.filter(|d| d.name == "tls")[0].rva"#
+        )?,
+        0x2300
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"pe.sections[1].addr"#)?,
+        module_addr + 0x2000
+    );
+
+    // Exports
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(
+                &mut scope,
+                r#"pe.exports.map(|e| [e.name, e.ordinal, e.addr])"#
+            )?
+            .into_iter()
+            .map(|e| e.cast::<rhai::Array>())
+            .map(|e| (
+                e[0].clone().cast::<ImmutableString>().to_string(),
+                e[1].as_int().unwrap(),
+                e[2].clone().cast::<Address>()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            ("CreateFileW".into(), 1, module_addr + 0x1100),
+            ("ReadFile".into(), 2, module_addr + 0x1200)
+        ]
+    );
+
+    // Imports, by name and by ordinal
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"let i = pe.imports[0]; `${i.dll}!${i.name} ${i.hint} ${i.ordinal}`"#
+        )?,
+        "kernel32.dll!Sleep 7 "
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"pe.imports[1].ordinal"#)?,
+        16
+    );
+    assert!(engine.eval_with_scope::<bool>(&mut scope, r#"pe.imports[1].name == ()"#)?);
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"pe.imports[1].slot"#)?,
+        module_addr + 0x2168
+    );
+
+    // Relocations, padding is skipped
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"pe.relocations.map(|r| r.kind)"#)?
+            .into_iter()
+            .map(|k| k.as_int().unwrap())
+            .collect::<Vec<_>>(),
+        vec![10, 10, 3]
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"pe.relocations[2].addr"#)?,
+        module_addr + 0x1030
+    );
+
+    // TLS
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"pe.tls.start"#)?,
+        module_addr + 0x2400
+    );
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"pe.tls.callbacks"#)?
+            .into_iter()
+            .map(|c| c.cast::<Address>())
+            .collect::<Vec<_>>(),
+        vec![module_addr + 0x1300]
+    );
+
+    // Debug directory
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"pe.debug_entries[0].timestamp"#)?,
+        0x12345678
+    );
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"let cv = pe.debug_entries[0].codeview; `${cv.guid} ${cv.age} ${cv.path}`"#
+        )?,
+        "03020100-0504-0706-0809-0A0B0C0D0E0F 3 test.pdb"
+    );
+
+    // Anything but a PE image is rejected
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.pe(MODULE + 0x1000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("no DOS signature"));
+
+    // Malformed headers are errors, not panics
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.pe(MODULE + 0x4000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("malformed PE"));
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.pe(MODULE + 0x8000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("exceeds the limit"));
+
+    Ok(())
+}
+
+/// Build a minimal ELF64 shared object as it would be mapped at `base`.
+fn synthetic_elf(base: Address) -> Vec<u8> {
+    fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+    fn put16(image: &mut [u8], offset: usize, val: u16) {
+        put(image, offset, &val.to_le_bytes());
+    }
+    fn put32(image: &mut [u8], offset: usize, val: u32) {
+        put(image, offset, &val.to_le_bytes());
+    }
+    fn put64(image: &mut [u8], offset: usize, val: u64) {
+        put(image, offset, &val.to_le_bytes());
+    }
+
+    let mut image = vec![0u8; 0x2000];
+
+    // ELF header
+    put(&mut image, 0, b"\x7fELF\x02\x01\x01");
+    put16(&mut image, 0x10, 3); // e_type, ET_DYN
+    put16(&mut image, 0x12, 0x3e); // e_machine, x86_64
+    put32(&mut image, 0x14, 1); // e_version
+    put64(&mut image, 0x18, 0x1000); // e_entry
+    put64(&mut image, 0x20, 0x40); // e_phoff
+    put16(&mut image, 0x34, 0x40); // e_ehsize
+    put16(&mut image, 0x36, 0x38); // e_phentsize
+    put16(&mut image, 0x38, 3); // e_phnum
+
+    // Program headers
+    let phdr = |image: &mut [u8], idx: usize, kind: u32, flags: u32, vaddr: u64, size: u64| {
+        let ph = 0x40 + idx * 0x38;
+        put32(image, ph, kind);
+        put32(image, ph + 0x4, flags);
+        put64(image, ph + 0x8, vaddr);
+        put64(image, ph + 0x10, vaddr);
+        put64(image, ph + 0x18, vaddr);
+        put64(image, ph + 0x20, size);
+        put64(image, ph + 0x28, size);
+        put64(image, ph + 0x30, 8);
+    };
+    phdr(&mut image, 0, 1, 5, 0, 0x2000); // PT_LOAD
+    phdr(&mut image, 1, 2, 6, 0x1800, 0x100); // PT_DYNAMIC
+    phdr(&mut image, 2, 4, 4, 0x1a00, 0x38); // PT_NOTE
+
+    // Dynamic section, the loader has already relocated `DT_STRTAB` in place
+    let dynamic: [(u64, u64); 8] = [
+        (1, 1),                       // DT_NEEDED
+        (1, 11),                      // DT_NEEDED
+        (14, 21),                     // DT_SONAME
+        (5, base.to_umem() + 0x1200), // DT_STRTAB
+        (10, 0x100),                  // DT_STRSZ
+        (6, 0x1100),                  // DT_SYMTAB
+        (11, 24),                     // DT_SYMENT
+        (0x6ffffef5, 0x1300),         // DT_GNU_HASH
+    ];
+    for (i, (tag, val)) in dynamic.iter().enumerate() {
+        put64(&mut image, 0x1800 + i * 16, *tag);
+        put64(&mut image, 0x1808 + i * 16, *val);
+    }
+
+    // Dynamic symbols, `puts` is undefined
+    let sym =
+        |image: &mut [u8], idx: usize, name: u32, info: u8, shndx: u16, value: u64, size: u64| {
+            let sym = 0x1100 + idx * 24;
+            put32(image, sym, name);
+            image[sym + 4] = info;
+            put16(image, sym + 6, shndx);
+            put64(image, sym + 8, value);
+            put64(image, sym + 16, size);
+        };
+    sym(&mut image, 1, 52, 0x12, 0, 0, 0);
+    sym(&mut image, 2, 32, 0x12, 1, 0x1000, 0x20);
+    sym(&mut image, 3, 42, 0x21, 2, 0x1400, 8);
+
+    // Dynamic strings
+    put(
+        &mut image,
+        0x1200,
+        b"\0libc.so.6\0libm.so.6\0libtest.so\0test_func\0test_data\0puts\0",
+    );
+
+    // GNU hash table with a single bucket chaining the two defined symbols
+    put32(&mut image, 0x1300, 1); // nbuckets
+    put32(&mut image, 0x1304, 2); // symoffset
+    put32(&mut image, 0x1308, 1); // bloom_size
+    put32(&mut image, 0x1318, 2); // buckets
+    put32(&mut image, 0x131c, 0x10); // chains
+    put32(&mut image, 0x1320, 0x21);
+
+    // Notes, an ABI tag followed by the build-id
+    put32(&mut image, 0x1a00, 4);
+    put32(&mut image, 0x1a04, 16);
+    put32(&mut image, 0x1a08, 1); // NT_GNU_ABI_TAG
+    put(&mut image, 0x1a0c, b"GNU\0");
+    put32(&mut image, 0x1a20, 4);
+    put32(&mut image, 0x1a24, 8);
+    put32(&mut image, 0x1a28, 3); // NT_GNU_BUILD_ID
+    put(&mut image, 0x1a2c, b"GNU\0");
+    put(
+        &mut image,
+        0x1a30,
+        &[0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04],
+    );
+
+    image
+}
+
+#[test]
+fn test_process_elf() -> Result<(), Box<EvalAltResult>> {
+    // Create dummy process with a module mapping the synthetic image.
+    let mut prc = dummy_process();
+    let module_addr = prc.proc.info.address + 0x10000;
+    prc.proc.modules.push(ModuleInfo {
+        address: Address::NULL,
+        parent_process: prc.proc.info.address,
+        base: module_addr,
+        size: 0x2000,
+        name: "libtest.so".into(),
+        path: "/usr/lib/libtest.so".into(),
+        arch: ArchitectureIdent::X86(64, false),
+    });
+    prc.write_raw(module_addr, &synthetic_elf(module_addr))
+        .unwrap();
+    // A copy whose program headers would lie past the end of the address space
+    let mut corrupt = synthetic_elf(module_addr + 0x4000);
+    corrupt[0x20..0x28].copy_from_slice(&0xffffffffffffff00u64.to_le_bytes());
+    prc.write_raw(module_addr + 0x4000, &corrupt).unwrap();
+    // A copy whose program headers are far too large
+    let mut corrupt = synthetic_elf(module_addr + 0x8000);
+    corrupt[0x36..0x38].copy_from_slice(&0xffffu16.to_le_bytes());
+    prc.write_raw(module_addr + 0x8000, &corrupt).unwrap();
+    // A copy whose symbols are far too large
+    let mut corrupt = synthetic_elf(module_addr + 0xc000);
+    corrupt[0x1868..0x1870].copy_from_slice(&0x100000u64.to_le_bytes());
+    prc.write_raw(module_addr + 0xc000, &corrupt).unwrap();
+
+    let (engine, mut scope) = setup(prc);
+    scope.push_constant("MODULE", module_addr);
+
+    engine.eval_with_scope::<()>(
+        &mut scope,
+        r#"let elf = PROCESS.elf(PROCESS.mod("libtest.so"));"#,
+    )?;
+
+    // Header and program headers
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"elf.header.machine"#)?,
+        0x3e
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"elf.header.entry"#)?,
+        module_addr + 0x1000
+    );
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"elf.program_headers.map(|ph| ph.kind)"#)?
+            .into_iter()
+            .map(|k| k.as_int().unwrap())
+            .collect::<Vec<_>>(),
+        vec![1, 2, 4]
+    );
+    assert_eq!(
+        engine.eval_with_scope::<rhai::INT>(&mut scope, r#"elf.dynamic.len()"#)?,
+        8
+    );
+
+    // Libraries
+    assert_eq!(
+        engine
+            .eval_with_scope::<rhai::Array>(&mut scope, r#"elf.needed"#)?
+            .into_iter()
+            .map(|n| n.cast::<ImmutableString>().to_string())
+            .collect::<Vec<_>>(),
+        vec!["libc.so.6", "libm.so.6"]
+    );
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(&mut scope, r#"elf.soname"#)?,
+        "libtest.so"
+    );
+
+    // Exports, undefined symbols are skipped
+    assert_eq!(
+        engine.eval_with_scope::<String>(
+            &mut scope,
+            r#"elf.exports.map(|s| `${s.name}:${s.kind}:${s.bind}:${s.size}`).reduce(|acc, s| acc + " " + s, "")"#
+        )?,
+        " test_func:func:global:32 test_data:object:weak:8"
+    );
+    assert_eq!(
+        engine.eval_with_scope::<Address>(&mut scope, r#"elf.exports[1].addr"#)?,
+        module_addr + 0x1400
+    );
+
+    // Build-id
+    assert_eq!(
+        engine.eval_with_scope::<ImmutableString>(&mut scope, r#"elf.build_id"#)?,
+        "deadbeef01020304"
+    );
+
+    // Anything but an ELF image is rejected
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.elf(MODULE + 0x1000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("no ELF signature"));
+
+    // Malformed headers are errors, not panics
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.elf(MODULE + 0x4000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("malformed ELF"));
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.elf(MODULE + 0x8000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid program header size"));
+    let err = engine
+        .eval_with_scope::<rhai::Map>(&mut scope, r#"PROCESS.elf(MODULE + 0xc000)"#)
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid symbol size"));
+
+    Ok(())
+}